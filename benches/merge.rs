@@ -0,0 +1,63 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lob::{
+    ops::{update_strategies::ReplaceOrRemove, Merge, Update},
+    Bids, PriceAndQuantity,
+};
+
+fn book(levels: usize) -> Bids {
+    (0..levels)
+        .map(|i| PriceAndQuantity(i as f64, 1.))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+// Touches every `step`th price, half of them removals, plus as many new prices in between.
+fn update(levels: usize, step: usize) -> Vec<PriceAndQuantity<f64, f64>> {
+    (0..levels)
+        .step_by(step)
+        .flat_map(|i| {
+            let quantity = if i % 2 == 0 { 0. } else { 2. };
+            [
+                PriceAndQuantity(i as f64, quantity),
+                PriceAndQuantity(i as f64 + 0.5, 3.),
+            ]
+        })
+        .collect()
+}
+
+fn apply_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_update");
+    let levels = 5_000;
+    for step in [100, 20, 5] {
+        let bids = book(levels);
+        let update = update(levels, step);
+        let size = update.len();
+
+        group.bench_with_input(BenchmarkId::new("process", size), &update, |b, update| {
+            b.iter_batched(
+                || bids.clone(),
+                |mut bids| {
+                    for level in update {
+                        Update::<ReplaceOrRemove>::process(&mut bids, *level);
+                    }
+                    black_box(bids)
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("merge", size), &update, |b, update| {
+            b.iter_batched(
+                || bids.clone(),
+                |mut bids| {
+                    Merge::<ReplaceOrRemove>::merge(&mut bids, update.iter().copied());
+                    black_box(bids)
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, apply_update);
+criterion_main!(benches);
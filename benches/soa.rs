@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lob::{soa::SoaBids, Bids, PriceAndQuantity};
+
+fn book(levels: usize) -> Bids {
+    (0..levels)
+        .map(|i| PriceAndQuantity(i as f64 * 0.01, 1. + (i % 7) as f64))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn cumulative_quantity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cumulative_quantity");
+    for levels in [100, 1_000, 10_000] {
+        let aos = book(levels);
+        let soa = SoaBids::from(&aos);
+        // Scan the whole side.
+        let price = 0.;
+
+        group.bench_with_input(BenchmarkId::new("aos", levels), &aos, |b, aos| {
+            b.iter(|| {
+                let from = aos.partition_point(|level| level.0 < black_box(price));
+                aos[from..].iter().fold(0., |acc, level| acc + level.1)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("soa", levels), &soa, |b, soa| {
+            b.iter(|| soa.cumulative_quantity(black_box(&price)))
+        });
+    }
+    group.finish();
+}
+
+fn vwap(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vwap");
+    for levels in [100, 1_000, 10_000] {
+        let aos = book(levels);
+        let soa = SoaBids::from(&aos);
+        let price = 0.;
+
+        group.bench_with_input(BenchmarkId::new("aos", levels), &aos, |b, aos| {
+            b.iter(|| {
+                let from = aos.partition_point(|level| level.0 < black_box(price));
+                let (notional, quantity) = aos[from..].iter().fold((0., 0.), |(n, q), level| {
+                    (n + level.0 * level.1, q + level.1)
+                });
+                notional / quantity
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("soa", levels), &soa, |b, soa| {
+            b.iter(|| soa.vwap(black_box(&price)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cumulative_quantity, vwap);
+criterion_main!(benches);
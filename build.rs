@@ -4,7 +4,7 @@ fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     tonic_build::configure()
         .file_descriptor_set_path(out_dir.join("limitorderbook_descriptor.bin"))
-        .compile(&["proto/limit_order_book.proto"], &["proto"])
+        .compile_protos(&["proto/limit_order_book.proto"], &["proto"])
         .unwrap();
 }
 
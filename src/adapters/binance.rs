@@ -0,0 +1,428 @@
+//! Binance market streams, raw or multiplexed through the combined stream envelope `{"stream": .., "data": ..}`.
+//!
+//! Payloads are dispatched on their `e` field. Spot `@depth<levels>` and `@bookTicker` payloads have none,
+//! for those the stream name decides, or on a raw socket the shape of the payload.
+//!
+//! The dispatch only peeks at those fields, skipping the levels, then the payload is deserialized straight into its
+//! type. It works on the JSON text, [StreamEvent] and [MarketEvent] only deserialize from [serde_json].
+
+use crate::limit_order_book::{Execution, MarketUpdate, Venue};
+use crate::price_and_quantity::from_str_or_number;
+use crate::{BookTicker, DepthUpdate, OrderType, PartialDepth};
+use serde::de::{self, IgnoredAny};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+
+/// `<symbol>@trade` stream.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Trade {
+    /// Milliseconds since the epoch.
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "from_str_or_number")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "from_str_or_number")]
+    pub quantity: f64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+/// `<symbol>@aggTrade` stream, the fills of one taker order at one price.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AggTrade {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "from_str_or_number")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "from_str_or_number")]
+    pub quantity: f64,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+/// `<symbol>@kline_<interval>` stream.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Kline {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub candle: Candle,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Candle {
+    #[serde(rename = "t")]
+    pub open_time: u64,
+    #[serde(rename = "T")]
+    pub close_time: u64,
+    #[serde(rename = "i")]
+    pub interval: String,
+    /// -1 when there was no trade.
+    #[serde(rename = "f")]
+    pub first_trade_id: i64,
+    #[serde(rename = "L")]
+    pub last_trade_id: i64,
+    #[serde(rename = "o", deserialize_with = "from_str_or_number")]
+    pub open: f64,
+    #[serde(rename = "c", deserialize_with = "from_str_or_number")]
+    pub close: f64,
+    #[serde(rename = "h", deserialize_with = "from_str_or_number")]
+    pub high: f64,
+    #[serde(rename = "l", deserialize_with = "from_str_or_number")]
+    pub low: f64,
+    #[serde(rename = "v", deserialize_with = "from_str_or_number")]
+    pub volume: f64,
+    #[serde(rename = "q", deserialize_with = "from_str_or_number")]
+    pub quote_volume: f64,
+    #[serde(rename = "n")]
+    pub trades: u64,
+    /// The candle won't change anymore.
+    #[serde(rename = "x")]
+    pub closed: bool,
+}
+
+/// The taker sold into the bids when the buyer was the maker.
+fn aggressor(buyer_is_maker: bool) -> OrderType {
+    if buyer_is_maker {
+        OrderType::Sell
+    } else {
+        OrderType::Buy
+    }
+}
+
+impl Trade {
+    pub fn aggressor(&self) -> OrderType {
+        aggressor(self.buyer_is_maker)
+    }
+}
+
+impl AggTrade {
+    pub fn aggressor(&self) -> OrderType {
+        aggressor(self.buyer_is_maker)
+    }
+}
+
+impl From<&Trade> for Execution {
+    fn from(trade: &Trade) -> Self {
+        Execution {
+            price: trade.price,
+            quantity: trade.quantity,
+            aggressor: trade.aggressor(),
+            trade_id: trade.trade_id,
+            time: trade.trade_time,
+        }
+    }
+}
+
+/// Identified by the aggregate trade id, `first_trade_id..=last_trade_id` are the trades it covers.
+impl From<&AggTrade> for Execution {
+    fn from(trade: &AggTrade) -> Self {
+        Execution {
+            price: trade.price,
+            quantity: trade.quantity,
+            aggressor: trade.aggressor(),
+            trade_id: trade.agg_trade_id,
+            time: trade.trade_time,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarketEvent {
+    DepthUpdate(DepthUpdate),
+    PartialDepth(PartialDepth),
+    Trade(Trade),
+    AggTrade(AggTrade),
+    BookTicker(BookTicker),
+    Kline(Kline),
+}
+
+/// A [MarketEvent] with the name of the stream it came from, [None] on a raw socket.
+/// Partial depth payloads carry no symbol, the stream name is the only way to tell them apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamEvent {
+    pub stream: Option<String>,
+    /// `s` of the payload, or else the upper cased symbol of the stream name.
+    pub symbol: Option<String>,
+    /// `E` of the payload, milliseconds since the epoch.
+    pub event_time: Option<u64>,
+    pub event: MarketEvent,
+}
+
+impl StreamEvent {
+    /// Depth updates and partial depths as a [MarketUpdate], [None] for other events.
+    /// Updates carrying `pu` are attributed to the USD-M futures venue.
+    pub fn market_update(&self, receive_time: u64) -> Option<MarketUpdate> {
+        let symbol = self.symbol.clone().unwrap_or_default();
+        let exchange_time = self.event_time.unwrap_or_default();
+        match &self.event {
+            MarketEvent::DepthUpdate(update) => {
+                let venue = match update.previous_update_id {
+                    Some(_) => Venue::BinanceUsdMFutures,
+                    None => Venue::Binance,
+                };
+                Some(MarketUpdate::from_depth_update(
+                    venue,
+                    symbol,
+                    update.transaction_time.unwrap_or(exchange_time),
+                    receive_time,
+                    update,
+                    false,
+                ))
+            }
+            MarketEvent::PartialDepth(depth) => {
+                let update = DepthUpdate {
+                    first_update_id: depth.last_update_id,
+                    last_update_id: depth.last_update_id,
+                    bids: depth.bids.clone(),
+                    asks: depth.asks.clone(),
+                    ..Default::default()
+                };
+                Some(MarketUpdate::from_depth_update(
+                    Venue::Binance,
+                    symbol,
+                    exchange_time,
+                    receive_time,
+                    &update,
+                    true,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The fields the dispatch looks at, of the envelope or of the payload, every other value is skipped.
+#[derive(Deserialize)]
+struct Peek<'a> {
+    stream: Option<String>,
+    #[serde(borrow)]
+    data: Option<&'a RawValue>,
+    e: Option<String>,
+    s: Option<String>,
+    #[serde(rename = "E")]
+    event_time: Option<u64>,
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: Option<IgnoredAny>,
+    u: Option<IgnoredAny>,
+    #[serde(rename = "A")]
+    best_ask_quantity: Option<IgnoredAny>,
+}
+
+fn peek<E: de::Error>(json: &RawValue) -> Result<Peek<'_>, E> {
+    serde_json::from_str(json.get()).map_err(E::custom)
+}
+
+impl<'de> Deserialize<'de> for StreamEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let message = Box::<RawValue>::deserialize(deserializer)?;
+        let mut payload = &*message;
+        let mut fields = peek(payload)?;
+        let stream = match (fields.stream.take(), fields.data) {
+            (Some(stream), Some(data)) => {
+                payload = data;
+                fields = peek(payload)?;
+                Some(stream)
+            }
+            _ => None,
+        };
+        let symbol = fields.s.take().or_else(|| {
+            stream
+                .as_deref()
+                .and_then(|stream| stream.split('@').next())
+                .map(str::to_uppercase)
+        });
+        let event_time = fields.event_time;
+        let event = MarketEvent::from_payload(stream.as_deref(), &fields, payload.get())
+            .map_err(de::Error::custom)?;
+        Ok(StreamEvent {
+            stream,
+            symbol,
+            event_time,
+            event,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for MarketEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        StreamEvent::deserialize(deserializer).map(|stream| stream.event)
+    }
+}
+
+/// Kind of a payload without an `e` field, from the stream name such as `btcusdt@depth20@100ms`.
+fn stream_kind(stream: &str) -> Option<&'static str> {
+    let mut parts = stream.split('@').skip(1);
+    match parts.next()? {
+        "bookTicker" => Some("bookTicker"),
+        depth => depth
+            .strip_prefix("depth")
+            .filter(|levels| !levels.is_empty() && levels.bytes().all(|b| b.is_ascii_digit()))
+            .map(|_| "partialDepth"),
+    }
+}
+
+impl MarketEvent {
+    fn from_payload(stream: Option<&str>, fields: &Peek, payload: &str) -> Result<Self, String> {
+        let kind = match (stream.and_then(stream_kind), &fields.e) {
+            (Some(kind), _) => kind,
+            (None, Some(kind)) => kind.as_str(),
+            (None, None) if fields.last_update_id.is_some() => "partialDepth",
+            (None, None) if fields.u.is_some() && fields.best_ask_quantity.is_some() => {
+                "bookTicker"
+            }
+            (None, None) => return Err("missing field `e`".to_owned()),
+        };
+        let event = match kind {
+            "depthUpdate" => serde_json::from_str(payload).map(MarketEvent::DepthUpdate),
+            "partialDepth" => serde_json::from_str(payload).map(MarketEvent::PartialDepth),
+            "trade" => serde_json::from_str(payload).map(MarketEvent::Trade),
+            "aggTrade" => serde_json::from_str(payload).map(MarketEvent::AggTrade),
+            "bookTicker" => serde_json::from_str(payload).map(MarketEvent::BookTicker),
+            "kline" => serde_json::from_str(payload).map(MarketEvent::Kline),
+            other => return Err(format!("unknown event type `{other}`")),
+        };
+        event.map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PriceAndQuantity;
+
+    fn combined(stream: &str, data: &str) -> StreamEvent {
+        serde_json::from_str(&format!(r#"{{"stream": "{stream}", "data": {data}}}"#)).unwrap()
+    }
+
+    #[test]
+    fn dispatches_depth_update() {
+        let data = r#"{"e": "depthUpdate", "E": 123456789, "s": "BNBBTC", "U": 157, "u": 160,
+            "b": [["0.0024", "10"]], "a": [["0.0026", "100"]]}"#;
+        let message = combined("bnbbtc@depth", data);
+        assert_eq!(message.stream.as_deref(), Some("bnbbtc@depth"));
+        let normalized = message.market_update(123456790).unwrap();
+        assert_eq!(normalized.venue, Venue::Binance);
+        assert_eq!(normalized.symbol, "BNBBTC");
+        assert_eq!(normalized.exchange_time, 123456789);
+        assert_eq!(normalized.changes.len(), 2);
+
+        let MarketEvent::DepthUpdate(update) = message.event else {
+            panic!("expected a depth update")
+        };
+        assert_eq!((update.first_update_id, update.last_update_id), (157, 160));
+        assert_eq!(*update.bids, [PriceAndQuantity(0.0024, 10.)]);
+
+        let raw: MarketEvent = serde_json::from_str(data).unwrap();
+        assert_eq!(raw, MarketEvent::DepthUpdate(update));
+    }
+
+    #[test]
+    fn dispatches_trades() {
+        let trade = combined(
+            "bnbbtc@trade",
+            r#"{"e": "trade", "E": 1672515782136, "s": "BNBBTC", "t": 12345, "p": "0.001",
+                "q": "100", "T": 1672515782136, "m": true, "M": true}"#,
+        );
+        let expected = Trade {
+            event_time: 1672515782136,
+            symbol: "BNBBTC".to_owned(),
+            trade_id: 12345,
+            price: 0.001,
+            quantity: 100.,
+            trade_time: 1672515782136,
+            buyer_is_maker: true,
+        };
+        assert_eq!(Execution::from(&expected).aggressor, OrderType::Sell);
+        assert_eq!(trade.event, MarketEvent::Trade(expected));
+
+        let agg_trade = combined(
+            "bnbbtc@aggTrade",
+            r#"{"e": "aggTrade", "E": 1672515782136, "s": "BNBBTC", "a": 12345, "p": "0.001",
+                "q": "100", "f": 100, "l": 105, "T": 1672515782136, "m": false, "M": true}"#,
+        );
+        let MarketEvent::AggTrade(agg_trade) = agg_trade.event else {
+            panic!("expected an aggregated trade")
+        };
+        assert_eq!(
+            (agg_trade.first_trade_id, agg_trade.last_trade_id),
+            (100, 105)
+        );
+        assert_eq!(agg_trade.aggressor(), OrderType::Buy);
+        assert_eq!(Execution::from(&agg_trade).trade_id, 12345);
+    }
+
+    #[test]
+    fn dispatches_kline() {
+        let kline = combined(
+            "bnbbtc@kline_1m",
+            r#"{"e": "kline", "E": 1672515782136, "s": "BNBBTC", "k": {"t": 1672515780000,
+                "T": 1672515839999, "s": "BNBBTC", "i": "1m", "f": 100, "L": 200, "o": "0.0010",
+                "c": "0.0020", "h": "0.0025", "l": "0.0015", "v": "1000", "n": 100, "x": false,
+                "q": "1.0000", "V": "500", "Q": "0.500", "B": "123456"}}"#,
+        );
+        let MarketEvent::Kline(kline) = kline.event else {
+            panic!("expected a kline")
+        };
+        assert_eq!(kline.candle.interval, "1m");
+        assert_eq!(kline.candle.high, 0.0025);
+        assert!(!kline.candle.closed);
+    }
+
+    #[test]
+    fn dispatches_payloads_without_event_type() {
+        let ticker = r#"{"u": 400900217, "s": "BNBUSDT", "b": "25.35190000", "B": "31.21000000",
+            "a": "25.36520000", "A": "40.66000000"}"#;
+        let depth =
+            r#"{"lastUpdateId": 160, "bids": [["0.0024", "10"]], "asks": [["0.0026", "100"]]}"#;
+
+        let message = combined("bnbusdt@bookTicker", ticker);
+        assert!(
+            matches!(message.event, MarketEvent::BookTicker(ref t) if t.update_id == 400900217)
+        );
+        let message = combined("bnbbtc@depth20@100ms", depth);
+        assert!(
+            matches!(message.event, MarketEvent::PartialDepth(ref d) if d.last_update_id == 160)
+        );
+        let normalized = message.market_update(0).unwrap();
+        assert!(normalized.snapshot);
+        assert_eq!(normalized.symbol, "BNBBTC");
+
+        let raw: MarketEvent = serde_json::from_str(ticker).unwrap();
+        assert!(matches!(raw, MarketEvent::BookTicker(_)));
+        let raw: MarketEvent = serde_json::from_str(depth).unwrap();
+        assert!(matches!(raw, MarketEvent::PartialDepth(_)));
+    }
+
+    #[test]
+    fn rejects_unknown_events() {
+        let error =
+            serde_json::from_str::<MarketEvent>(r#"{"e": "markPriceUpdate", "E": 1}"#).unwrap_err();
+        assert!(error.to_string().contains("markPriceUpdate"));
+        assert!(serde_json::from_str::<MarketEvent>(r#"{"E": 1}"#).is_err());
+    }
+}
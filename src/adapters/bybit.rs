@@ -0,0 +1,154 @@
+//! Bybit v5 `orderbook.{depth}.{symbol}` topic.
+//!
+//! Deltas chain through the update id `u`, mapped to both `first_update_id` and `last_update_id` so that the
+//! [Consecutive] rule applies. A snapshot, or a delta with `u == 1` after a service restart, resets the book.
+//! `seq` is the cross sequence used to compare books of different depths, it is kept but not checked.
+
+use super::{apply_in_sequence, update_side, Error};
+use crate::limit_order_book::sequence::Consecutive;
+use crate::limit_order_book::{MarketUpdate, Venue};
+use crate::{DepthUpdate, LimitOrderBook, PriceAndQuantity};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Message {
+    pub topic: String,
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    /// Milliseconds since the epoch.
+    pub ts: u64,
+    pub data: Data,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Snapshot,
+    Delta,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Data {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "b")]
+    pub bids: Vec<PriceAndQuantity<f64, f64>>,
+    #[serde(rename = "a")]
+    pub asks: Vec<PriceAndQuantity<f64, f64>>,
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    pub seq: u64,
+}
+
+impl Message {
+    /// The venue resends a full book after a restart as an update with id 1.
+    pub fn is_snapshot(&self) -> bool {
+        self.kind == Kind::Snapshot || self.data.update_id == 1
+    }
+
+    pub fn depth_update(&self) -> DepthUpdate {
+        DepthUpdate {
+            #[cfg(feature = "event")]
+            event: super::event(&self.topic, self.ts, &self.data.symbol),
+            first_update_id: self.data.update_id,
+            last_update_id: self.data.update_id,
+            bids: update_side(self.data.bids.clone()),
+            asks: update_side(self.data.asks.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn market_update(&self, receive_time: u64) -> MarketUpdate {
+        MarketUpdate::from_depth_update(
+            Venue::Bybit,
+            &self.data.symbol,
+            self.ts,
+            receive_time,
+            &self.depth_update(),
+            self.is_snapshot(),
+        )
+    }
+}
+
+pub fn book(snapshot: &Message) -> Result<LimitOrderBook, Error> {
+    if !snapshot.is_snapshot() {
+        return Err(Error::ExpectedSnapshot);
+    }
+    let mut book = LimitOrderBook::new();
+    book.apply(&snapshot.depth_update());
+    Ok(book)
+}
+
+/// Applies a delta on top of `book`, a snapshot replaces it, keeping its depth limit. On [Error::SequenceGap] the book must be resubscribed.
+pub fn apply(book: &mut LimitOrderBook, message: &Message) -> Result<(), Error> {
+    if message.is_snapshot() {
+        book.replace(self::book(message)?);
+        return Ok(());
+    }
+    apply_in_sequence::<Consecutive>(book, &message.depth_update())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(kind: &str, update_id: u64, bids: &str) -> Message {
+        serde_json::from_str(&format!(
+            r#"{{
+                "topic": "orderbook.50.BTCUSDT",
+                "type": "{kind}",
+                "ts": 1672304484978,
+                "data": {{
+                    "s": "BTCUSDT",
+                    "b": {bids},
+                    "a": [["16611.00", "0.029"]],
+                    "u": {update_id},
+                    "seq": 7961638724
+                }},
+                "cts": 1672304484976
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn snapshot_then_delta() {
+        let snapshot = message(
+            "snapshot",
+            18521288,
+            r#"[["16493.50", "0.006"], ["16493.00", "0.100"]]"#,
+        );
+        let mut book = book(&snapshot).unwrap();
+
+        let delta = message("delta", 18521289, r#"[["16493.50", "0"]]"#);
+        apply(&mut book, &delta).unwrap();
+        assert_eq!(**book.bids(), [PriceAndQuantity(16493., 0.1)]);
+        assert_eq!(book.update_id, 18521289);
+        #[cfg(feature = "event-symbol")]
+        assert_eq!(delta.depth_update().event.symbol, "BTCUSDT");
+    }
+
+    #[test]
+    fn gap_requires_resync() {
+        let mut book = book(&message("snapshot", 10, "[]")).unwrap();
+        let delta = message("delta", 12, "[]");
+        assert!(apply(&mut book, &delta).unwrap_err().requires_resync());
+    }
+
+    #[test]
+    fn snapshot_keeps_depth_limit() {
+        let mut book = LimitOrderBook::with_depth_limit(1);
+        let snapshot = message("snapshot", 10, r#"[["1", "1"], ["2", "1"]]"#);
+        apply(&mut book, &snapshot).unwrap();
+        assert_eq!(**book.bids(), [PriceAndQuantity(2., 1.)]);
+        assert_eq!(book.depth_limit().unwrap().levels, 1);
+    }
+
+    #[test]
+    fn restart_resets_book() {
+        let mut book = book(&message("snapshot", 10, r#"[["1", "1"]]"#)).unwrap();
+        apply(&mut book, &message("delta", 1, r#"[["2", "1"]]"#)).unwrap();
+        assert_eq!(**book.bids(), [PriceAndQuantity(2., 1.)]);
+        assert_eq!(book.update_id, 1);
+    }
+}
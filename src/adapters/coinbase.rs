@@ -0,0 +1,183 @@
+//! Coinbase Exchange `level2` channel.
+//!
+//! The channel carries no update ids: the caller numbers the updates it applies, typically `book.update_id + 1`,
+//! so that [DepthUpdate::skip_update] keeps working with [Market::Spot](crate::limit_order_book::sequence::Market::Spot).
+
+use super::{de_rfc3339_millis, update_side};
+use crate::limit_order_book::{MarketUpdate, Venue};
+use crate::price_and_quantity::from_str_or_number;
+use crate::{Asks, Bids, DepthUpdate, LimitOrderBook, PriceAndQuantity};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum Message {
+    #[serde(rename = "snapshot")]
+    Snapshot(Snapshot),
+    #[serde(rename = "l2update")]
+    L2Update(L2Update),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Snapshot {
+    pub product_id: String,
+    /// Milliseconds since the epoch, older snapshots don't carry it.
+    #[serde(default, deserialize_with = "de_option_rfc3339_millis")]
+    pub time: Option<u64>,
+    pub bids: Bids,
+    pub asks: Asks,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct L2Update {
+    pub product_id: String,
+    /// Milliseconds since the epoch.
+    #[serde(deserialize_with = "de_rfc3339_millis")]
+    pub time: u64,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// `["buy", "price", "size"]`, the size is the new total at that price, zero removes the level.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct Change(
+    pub Side,
+    #[serde(deserialize_with = "from_str_or_number")] pub f64,
+    #[serde(deserialize_with = "from_str_or_number")] pub f64,
+);
+
+fn de_option_rfc3339_millis<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    de_rfc3339_millis(deserializer).map(Some)
+}
+
+impl Snapshot {
+    pub fn into_book(self, update_id: u64) -> LimitOrderBook {
+        LimitOrderBook::from_sides(update_id, self.bids, self.asks)
+    }
+
+    pub fn market_update(&self, update_id: u64, receive_time: u64) -> MarketUpdate {
+        let update = DepthUpdate {
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
+            ..Default::default()
+        };
+        MarketUpdate::from_depth_update(
+            Venue::Coinbase,
+            &self.product_id,
+            self.time.unwrap_or_default(),
+            receive_time,
+            &update,
+            true,
+        )
+    }
+}
+
+impl L2Update {
+    pub fn into_depth_update(self, update_id: u64) -> DepthUpdate {
+        let (buys, sells): (Vec<_>, Vec<_>) = self
+            .changes
+            .iter()
+            .partition(|Change(side, ..)| *side == Side::Buy);
+        let levels = |changes: Vec<&Change>| {
+            changes
+                .into_iter()
+                .map(|Change(_, price, size)| PriceAndQuantity(*price, *size))
+                .collect()
+        };
+
+        DepthUpdate {
+            #[cfg(feature = "event")]
+            event: super::event("l2update", self.time, &self.product_id),
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: update_side(levels(buys)),
+            asks: update_side(levels(sells)),
+            ..Default::default()
+        }
+    }
+
+    pub fn market_update(&self, update_id: u64, receive_time: u64) -> MarketUpdate {
+        MarketUpdate::from_depth_update(
+            Venue::Coinbase,
+            &self.product_id,
+            self.time,
+            receive_time,
+            &self.clone().into_depth_update(update_id),
+            false,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::limit_order_book::sequence::Market;
+
+    const SNAPSHOT: &str = r#"{
+        "type": "snapshot",
+        "product_id": "BTC-USD",
+        "bids": [["10101.10", "0.45054140"], ["10101.00", "1.00000000"]],
+        "asks": [["10102.55", "0.57753524"]]
+    }"#;
+
+    const UPDATE: &str = r#"{
+        "type": "l2update",
+        "product_id": "BTC-USD",
+        "time": "2019-08-14T20:42:27.265Z",
+        "changes": [
+            ["buy", "10101.80000000", "0.162567"],
+            ["buy", "10101.00000000", "0.0"],
+            ["sell", "10102.55000000", "0.3"]
+        ]
+    }"#;
+
+    #[test]
+    fn snapshot_then_update() {
+        let Message::Snapshot(snapshot) = serde_json::from_str(SNAPSHOT).unwrap() else {
+            panic!("expected a snapshot");
+        };
+        let normalized = snapshot.market_update(0, 0);
+        assert!(normalized.snapshot);
+        let mut book = snapshot.into_book(0);
+        assert_eq!(
+            **book.bids(),
+            [
+                PriceAndQuantity(10101.0, 1.),
+                PriceAndQuantity(10101.1, 0.4505414)
+            ]
+        );
+
+        let Message::L2Update(update) = serde_json::from_str(UPDATE).unwrap() else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.time, 1565815347265);
+        let update = update.into_depth_update(book.update_id + 1);
+        assert!(!update.skip_update(Market::Spot, book.update_id));
+        #[cfg(feature = "event-symbol")]
+        assert_eq!(update.event.symbol, "BTC-USD");
+        #[cfg(feature = "event-time")]
+        assert_eq!(update.event.time, 1565815347265);
+
+        book.apply(&update);
+        assert_eq!(
+            **book.bids(),
+            [
+                PriceAndQuantity(10101.1, 0.4505414),
+                PriceAndQuantity(10101.8, 0.162567)
+            ]
+        );
+        assert_eq!(**book.asks(), [PriceAndQuantity(10102.55, 0.3)]);
+        assert_eq!(book.update_id, 1);
+    }
+}
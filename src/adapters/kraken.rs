@@ -0,0 +1,364 @@
+//! Kraken websocket `book` channel.
+//!
+//! Kraken sends no update ids, the integrity of the book is checked with the CRC32 checksum of its top 10 levels
+//! instead, see [Subscription::apply].
+
+use super::{update_side, Error};
+use crate::limit_order_book::{MarketUpdate, Venue};
+use crate::price_and_quantity::from_str_or_number;
+use crate::{DepthUpdate, LimitOrderBook, PriceAndQuantity};
+use serde::de::{self, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Levels hashed by the checksum, per side.
+const CHECKSUM_LEVELS: usize = 10;
+
+/// `[channelID, {..}, ({..},) channelName, pair]`, updates to both sides come as two payload objects.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BookMessage {
+    pub channel_id: u64,
+    pub channel_name: String,
+    pub pair: String,
+    /// Sent with `as`/`bs` instead of `a`/`b`.
+    pub snapshot: bool,
+    pub asks: Vec<Level>,
+    pub bids: Vec<Level>,
+    pub checksum: Option<u32>,
+}
+
+/// `["price", "volume", "timestamp"]`, updates may carry a trailing `"r"` for republished levels.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Level {
+    pub price: f64,
+    pub volume: f64,
+    /// Milliseconds since the epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct Decimal(#[serde(deserialize_with = "from_str_or_number")] f64);
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LevelVisitor;
+
+        impl<'de> Visitor<'de> for LevelVisitor {
+            type Value = Level;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a [price, volume, timestamp] level")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut next = |i| {
+                    seq.next_element::<Decimal>()?
+                        .map(|Decimal(value)| value)
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))
+                };
+                let (price, volume, timestamp) = (next(0)?, next(1)?, next(2)?);
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Level {
+                    price,
+                    volume,
+                    timestamp: (timestamp * 1000.) as u64,
+                })
+            }
+        }
+
+        deserializer.deserialize_seq(LevelVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    #[serde(rename = "as")]
+    snapshot_asks: Option<Vec<Level>>,
+    #[serde(rename = "bs")]
+    snapshot_bids: Option<Vec<Level>>,
+    a: Option<Vec<Level>>,
+    b: Option<Vec<Level>>,
+    #[serde(default, deserialize_with = "de_checksum")]
+    c: Option<u32>,
+}
+
+fn de_checksum<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    from_str_or_number(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Element {
+    Payload(Payload),
+    Name(String),
+}
+
+impl<'de> Deserialize<'de> for BookMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BookMessageVisitor;
+
+        impl<'de> Visitor<'de> for BookMessageVisitor {
+            type Value = BookMessage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a book channel message")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut message = BookMessage {
+                    channel_id: seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(0, &self))?,
+                    ..Default::default()
+                };
+                let mut names = Vec::with_capacity(2);
+                while let Some(element) = seq.next_element()? {
+                    match element {
+                        Element::Payload(payload) => {
+                            message.snapshot |=
+                                payload.snapshot_asks.is_some() || payload.snapshot_bids.is_some();
+                            let asks = payload.snapshot_asks.into_iter().chain(payload.a);
+                            let bids = payload.snapshot_bids.into_iter().chain(payload.b);
+                            message.asks.extend(asks.flatten());
+                            message.bids.extend(bids.flatten());
+                            message.checksum = payload.c.or(message.checksum);
+                        }
+                        Element::Name(name) => names.push(name),
+                    }
+                }
+                let [channel_name, pair]: [String; 2] = names
+                    .try_into()
+                    .map_err(|_| de::Error::custom("expected a channel name and a pair"))?;
+                message.channel_name = channel_name;
+                message.pair = pair;
+                Ok(message)
+            }
+        }
+
+        deserializer.deserialize_seq(BookMessageVisitor)
+    }
+}
+
+/// Parameters of a `book` subscription.
+/// The decimals are the pair's `pair_decimals` and `lot_decimals`, the checksum hashes prices and volumes formatted with them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Subscription {
+    pub depth: usize,
+    pub price_decimals: usize,
+    pub volume_decimals: usize,
+}
+
+impl BookMessage {
+    /// Latest timestamp of the levels, the message has none of its own.
+    pub fn time(&self) -> u64 {
+        self.asks
+            .iter()
+            .chain(&self.bids)
+            .map(|level| level.timestamp)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl Subscription {
+    /// Builds the book from a snapshot message, truncated to the subscribed depth.
+    pub fn book(&self, snapshot: BookMessage) -> Result<LimitOrderBook, Error> {
+        if !snapshot.snapshot {
+            return Err(Error::ExpectedSnapshot);
+        }
+        let mut book = LimitOrderBook::new();
+        book.apply(&self.depth_update(snapshot, 0));
+        book.truncate(self.depth);
+        Ok(book)
+    }
+
+    pub fn depth_update(&self, message: BookMessage, update_id: u64) -> DepthUpdate {
+        let levels = |levels: &[Level]| {
+            levels
+                .iter()
+                .map(|level| PriceAndQuantity(level.price, level.volume))
+                .collect()
+        };
+
+        DepthUpdate {
+            #[cfg(feature = "event")]
+            event: super::event(&message.channel_name, message.time(), &message.pair),
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: update_side(levels(&message.bids)),
+            asks: update_side(levels(&message.asks)),
+            ..Default::default()
+        }
+    }
+
+    pub fn market_update(
+        &self,
+        message: BookMessage,
+        update_id: u64,
+        receive_time: u64,
+    ) -> MarketUpdate {
+        let (pair, time, snapshot) = (message.pair.clone(), message.time(), message.snapshot);
+        let update = self.depth_update(message, update_id);
+        MarketUpdate::from_depth_update(Venue::Kraken, pair, time, receive_time, &update, snapshot)
+    }
+
+    /// Applies an update, drops the levels beyond the subscribed depth and verifies the checksum if one was sent.
+    /// A snapshot replaces the book, keeping its depth limit. On [Error::ChecksumMismatch] the book must be resubscribed.
+    pub fn apply(&self, book: &mut LimitOrderBook, message: BookMessage) -> Result<(), Error> {
+        if message.snapshot {
+            book.replace(self.book(message)?);
+            return Ok(());
+        }
+        let checksum = message.checksum;
+        book.apply(&self.depth_update(message, book.update_id + 1));
+        book.truncate(self.depth);
+
+        if let Some(expected) = checksum {
+            let computed = self.checksum(book);
+            if computed != expected {
+                return Err(Error::ChecksumMismatch { expected, computed });
+            }
+        }
+        Ok(())
+    }
+
+    /// CRC32 of the top 10 asks, best first, followed by the top 10 bids, best first.
+    /// Each level contributes its price then its volume, formatted with the pair's decimals,
+    /// without the decimal point and without leading zeros.
+    pub fn checksum(&self, book: &LimitOrderBook) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        let asks = book.asks().iter().rev().take(CHECKSUM_LEVELS);
+        let bids = book.bids().iter().rev().take(CHECKSUM_LEVELS);
+        for level in asks.chain(bids) {
+            hasher.update(digits(level.0, self.price_decimals).as_bytes());
+            hasher.update(digits(level.1, self.volume_decimals).as_bytes());
+        }
+        hasher.finalize()
+    }
+}
+
+fn digits(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value).replace('.', "");
+    formatted.trim_start_matches('0').to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SUBSCRIPTION: Subscription = Subscription {
+        depth: 10,
+        price_decimals: 5,
+        volume_decimals: 8,
+    };
+
+    const SNAPSHOT: &str = r#"[
+        0,
+        {
+            "as": [
+                ["5541.30000", "2.50700000", "1534614248.123678"],
+                ["5541.80000", "0.33000000", "1534614098.345543"]
+            ],
+            "bs": [
+                ["5541.20000", "1.52900000", "1534614248.765567"],
+                ["5539.90000", "0.30000000", "1534614241.769870"]
+            ]
+        },
+        "book-10",
+        "XBT/USD"
+    ]"#;
+
+    fn book() -> LimitOrderBook {
+        SUBSCRIPTION
+            .book(serde_json::from_str(SNAPSHOT).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshot() {
+        let book = book();
+        let message: BookMessage = serde_json::from_str(SNAPSHOT).unwrap();
+        let update = SUBSCRIPTION.market_update(message, 0, 1534614336000);
+        assert!(update.snapshot);
+        assert_eq!(update.exchange_time, 1534614248765);
+        let mut normalized = LimitOrderBook::new();
+        normalized.apply_market_update(&update);
+        assert_eq!(normalized, book);
+        assert_eq!(
+            **book.asks(),
+            [
+                PriceAndQuantity(5541.8, 0.33),
+                PriceAndQuantity(5541.3, 2.507)
+            ]
+        );
+        assert_eq!(
+            **book.bids(),
+            [
+                PriceAndQuantity(5539.9, 0.3),
+                PriceAndQuantity(5541.2, 1.529)
+            ]
+        );
+    }
+
+    #[test]
+    fn update_with_valid_checksum() {
+        let mut book = book();
+        let update = r#"[
+            1234,
+            {"a": [["5541.30000", "0.00000000", "1534614335.345903"]]},
+            {"b": [["5541.20000", "1.52900000", "1534614335.345903", "r"]], "c": "5174102"},
+            "book-10",
+            "XBT/USD"
+        ]"#;
+        let update: BookMessage = serde_json::from_str(update).unwrap();
+        assert_eq!(update.checksum, Some(5174102));
+        assert_eq!(update.asks[0].timestamp, 1534614335345);
+
+        SUBSCRIPTION.apply(&mut book, update).unwrap();
+        assert_eq!(**book.asks(), [PriceAndQuantity(5541.8, 0.33)]);
+        assert_eq!(book.update_id, 1);
+    }
+
+    #[test]
+    fn checksum_mismatch_requires_resync() {
+        let mut book = book();
+        let update = r#"[
+            1234,
+            {"a": [["5541.30000", "1.00000000", "1534614335.345903"]], "c": "5174102"},
+            "book-10",
+            "XBT/USD"
+        ]"#;
+        let error = SUBSCRIPTION
+            .apply(&mut book, serde_json::from_str(update).unwrap())
+            .unwrap_err();
+        assert!(error.requires_resync());
+    }
+
+    #[test]
+    fn truncates_to_depth() {
+        let subscription = Subscription {
+            depth: 1,
+            ..SUBSCRIPTION
+        };
+        let book = subscription
+            .book(serde_json::from_str(SNAPSHOT).unwrap())
+            .unwrap();
+        assert_eq!(**book.asks(), [PriceAndQuantity(5541.3, 2.507)]);
+        assert_eq!(**book.bids(), [PriceAndQuantity(5541.2, 1.529)]);
+    }
+}
@@ -0,0 +1,206 @@
+//! Venue specific feeds mapped onto [LimitOrderBook](crate::LimitOrderBook) and [DepthUpdate](crate::DepthUpdate).
+
+pub mod binance;
+pub mod bybit;
+pub mod coinbase;
+pub mod kraken;
+pub mod okx;
+
+#[cfg(feature = "event")]
+use crate::limit_order_book::event::Event;
+use crate::limit_order_book::sequence::{Continuity, SequenceRule};
+use crate::ops::update_side;
+use crate::{DepthUpdate, LimitOrderBook};
+use serde::{de, Deserialize, Deserializer};
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// A message other than a snapshot arrived before the book was built.
+    ExpectedSnapshot,
+    /// The book computed locally no longer matches the venue's checksum.
+    ChecksumMismatch { expected: u32, computed: u32 },
+    /// Updates between the book and this update were missed.
+    SequenceGap {
+        last_book_id: u64,
+        first_update_id: u64,
+    },
+}
+
+impl Error {
+    /// The local book can't be trusted anymore, resubscribe to get a new snapshot.
+    pub fn requires_resync(&self) -> bool {
+        match self {
+            Error::ExpectedSnapshot => false,
+            Error::ChecksumMismatch { .. } | Error::SequenceGap { .. } => true,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ExpectedSnapshot => write!(f, "expected a snapshot"),
+            Error::ChecksumMismatch { expected, computed } => write!(
+                f,
+                "checksum mismatch, expected {expected} but computed {computed}; resync required"
+            ),
+            Error::SequenceGap {
+                last_book_id,
+                first_update_id,
+            } => write!(
+                f,
+                "sequence gap, book at {last_book_id} but update starts at {first_update_id}; resync required"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Applies `update` if it follows the book under the rule `R`, stale updates are dropped.
+pub(crate) fn apply_in_sequence<R: SequenceRule>(
+    book: &mut LimitOrderBook,
+    update: &DepthUpdate,
+) -> Result<(), Error> {
+    match update.continuity::<R>(book.update_id) {
+        Continuity::Continuous => {
+            book.apply(update);
+            Ok(())
+        }
+        Continuity::Stale => Ok(()),
+        Continuity::Gap => Err(Error::SequenceGap {
+            last_book_id: book.update_id,
+            first_update_id: update.first_update_id,
+        }),
+    }
+}
+
+#[cfg(feature = "event")]
+pub(crate) fn event(_id: &str, _time: u64, _symbol: &str) -> Event {
+    Event {
+        #[cfg(feature = "event-id")]
+        id: _id.to_owned(),
+        #[cfg(feature = "event-time")]
+        time: _time,
+        #[cfg(feature = "event-symbol")]
+        symbol: _symbol.to_owned(),
+    }
+}
+
+/// Milliseconds since the epoch of an RFC 3339 UTC timestamp such as `2019-08-14T20:42:27.265Z`.
+/// Digits past the millisecond are truncated, offsets other than `Z`/`+00:00` are rejected.
+pub fn rfc3339_millis(timestamp: &str) -> Option<u64> {
+    let b = timestamp.as_bytes();
+    let number = |from: usize, to: usize| -> Option<u64> {
+        let digits = b.get(from..to)?;
+        digits.iter().try_fold(0u64, |acc, d| {
+            d.is_ascii_digit().then(|| acc * 10 + u64::from(d - b'0'))
+        })
+    };
+    let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+    if separators.iter().any(|(i, c)| b.get(*i) != Some(c))
+        || !matches!(b.get(10), Some(b'T' | b't' | b' '))
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hour, minute, second) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = &b[19..];
+    let mut millis = 0;
+    if let Some((b'.', fraction)) = rest.split_first() {
+        let digits = fraction.iter().take_while(|d| d.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        millis = fraction[..digits.min(3)]
+            .iter()
+            .fold(0, |acc, d| acc * 10 + u64::from(d - b'0'))
+            * 10u64.pow(3 - digits.min(3) as u32);
+        rest = &fraction[digits..];
+    }
+    if !matches!(rest, b"Z" | b"z" | b"+00:00") {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day)?;
+    Some(((days * 24 + hour) * 60 + minute) * 60_000 + second * 1000 + millis)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days between 1970-01-01 and the given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+/// `deserialize_with` helper for [rfc3339_millis].
+pub(crate) fn de_rfc3339_millis<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = String::deserialize(deserializer)?;
+    rfc3339_millis(&timestamp).ok_or_else(|| {
+        de::Error::invalid_value(
+            de::Unexpected::Str(&timestamp),
+            &"an RFC 3339 UTC timestamp",
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339() {
+        assert_eq!(
+            rfc3339_millis("2019-08-14T20:42:27.265Z"),
+            Some(1565815347265)
+        );
+        assert_eq!(
+            rfc3339_millis("2019-08-14T20:42:27.265123Z"),
+            Some(1565815347265)
+        );
+        assert_eq!(
+            rfc3339_millis("2024-02-29T23:59:59.999+00:00"),
+            Some(1709251199999)
+        );
+        assert_eq!(rfc3339_millis("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(rfc3339_millis("2019-08-14T20:42:27.265+01:00"), None);
+        assert_eq!(rfc3339_millis("2019-13-14T20:42:27Z"), None);
+        assert_eq!(rfc3339_millis("2019-02-31T00:00:00.000Z"), None);
+        assert_eq!(rfc3339_millis("2019-02-29T00:00:00Z"), None);
+        assert_eq!(rfc3339_millis("1900-02-29T00:00:00Z"), None);
+        assert_eq!(rfc3339_millis("2000-02-29T00:00:00Z"), Some(951782400000));
+        assert_eq!(rfc3339_millis("2019-04-31T00:00:00Z"), None);
+        assert_eq!(rfc3339_millis("not a timestamp"), None);
+    }
+}
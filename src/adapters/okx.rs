@@ -0,0 +1,368 @@
+//! OKX `books` channel.
+//!
+//! Updates chain through `prevSeqId`/`seqId` and carry a signed CRC32 checksum of the top 25 levels.
+//! The ids are mapped so that `first_update_id` is `prevSeqId + 1` and `last_update_id` is `seqId`,
+//! the [Consecutive] rule then checks that `prevSeqId` is the book's `seqId`.
+//!
+//! The checksum hashes the price and size strings as OKX sent them, e.g. `"0.10"`, which a double can't tell from
+//! `"0.1"`. [OkxBook] keeps them next to the book.
+
+use super::{apply_in_sequence, update_side, Error};
+use crate::limit_order_book::sequence::{Consecutive, Continuity};
+use crate::limit_order_book::{MarketUpdate, Venue};
+use crate::price_and_quantity::from_str_or_number;
+use crate::{DepthUpdate, LimitOrderBook, PriceAndQuantity};
+use serde::de::{self, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Levels hashed by the checksum, per side.
+const CHECKSUM_LEVELS: usize = 25;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct BooksMessage {
+    pub arg: Arg,
+    pub action: Action,
+    pub data: Vec<Book>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Arg {
+    pub channel: String,
+    pub inst_id: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Snapshot,
+    Update,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Book {
+    pub asks: Vec<Level>,
+    pub bids: Vec<Level>,
+    /// Milliseconds since the epoch.
+    #[serde(deserialize_with = "from_str_or_number")]
+    pub ts: u64,
+    pub checksum: Option<i32>,
+    /// -1 on snapshots.
+    pub prev_seq_id: i64,
+    pub seq_id: u64,
+}
+
+/// `["price", "size", "0", "orders"]`, only price and size are kept, with the strings they were sent as.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Level {
+    pub price: f64,
+    pub size: f64,
+    pub raw: RawLevel,
+}
+
+/// Price and size as sent, hashed by the checksum.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct RawLevel {
+    pub price: String,
+    pub size: String,
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LevelVisitor;
+
+        impl<'de> Visitor<'de> for LevelVisitor {
+            type Value = Level;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a [price, size, ..] level")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut next = |i| {
+                    let raw: String = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                    let value = raw.parse().map_err(de::Error::custom)?;
+                    Ok((value, raw))
+                };
+                let ((price, raw_price), (size, raw_size)) = (next(0)?, next(1)?);
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Level {
+                    price,
+                    size,
+                    raw: RawLevel {
+                        price: raw_price,
+                        size: raw_size,
+                    },
+                })
+            }
+        }
+
+        deserializer.deserialize_seq(LevelVisitor)
+    }
+}
+
+fn levels(levels: &[Level]) -> Vec<PriceAndQuantity<f64, f64>> {
+    levels
+        .iter()
+        .map(|level| PriceAndQuantity(level.price, level.size))
+        .collect()
+}
+
+impl Book {
+    #[cfg_attr(not(feature = "event"), allow(unused_variables))]
+    pub fn depth_update(&self, arg: &Arg) -> DepthUpdate {
+        DepthUpdate {
+            #[cfg(feature = "event")]
+            event: super::event(&arg.channel, self.ts, &arg.inst_id),
+            first_update_id: (self.prev_seq_id + 1) as u64,
+            last_update_id: self.seq_id,
+            bids: update_side(levels(&self.bids)),
+            asks: update_side(levels(&self.asks)),
+            ..Default::default()
+        }
+    }
+}
+
+impl BooksMessage {
+    /// One update per entry of `data`, checksums are dropped.
+    pub fn market_updates(&self, receive_time: u64) -> Vec<MarketUpdate> {
+        self.data
+            .iter()
+            .map(|book| {
+                MarketUpdate::from_depth_update(
+                    Venue::Okx,
+                    &self.arg.inst_id,
+                    book.ts,
+                    receive_time,
+                    &book.depth_update(&self.arg),
+                    self.action == Action::Snapshot,
+                )
+            })
+            .collect()
+    }
+}
+
+/// The book with the strings of its levels as OKX sent them, by the bits of their price.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct OkxBook {
+    book: LimitOrderBook,
+    raw_bids: HashMap<u64, RawLevel>,
+    raw_asks: HashMap<u64, RawLevel>,
+}
+
+impl OkxBook {
+    pub fn book(&self) -> &LimitOrderBook {
+        &self.book
+    }
+
+    pub fn into_book(self) -> LimitOrderBook {
+        self.book
+    }
+
+    /// Keeps the strings of the levels of an applied update, removed levels are dropped.
+    fn record(&mut self, update: &Book) {
+        for (raw, levels) in [
+            (&mut self.raw_bids, &update.bids),
+            (&mut self.raw_asks, &update.asks),
+        ] {
+            for level in levels {
+                if level.size == 0. {
+                    raw.remove(&level.price.to_bits());
+                } else {
+                    raw.insert(level.price.to_bits(), level.raw.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Builds the book from a snapshot message and verifies its checksum.
+pub fn book(message: &BooksMessage) -> Result<OkxBook, Error> {
+    let (Action::Snapshot, [snapshot]) = (message.action, message.data.as_slice()) else {
+        return Err(Error::ExpectedSnapshot);
+    };
+    let mut book = OkxBook::default();
+    book.book.apply(&snapshot.depth_update(&message.arg));
+    book.record(snapshot);
+    verify(&book, snapshot.checksum)?;
+    Ok(book)
+}
+
+/// Applies every update of the message in sequence and verifies the checksum after each one.
+/// A snapshot replaces the book, keeping its depth limit. On error the book must be resubscribed.
+pub fn apply(book: &mut OkxBook, message: &BooksMessage) -> Result<(), Error> {
+    if message.action == Action::Snapshot {
+        let snapshot = self::book(message)?;
+        book.book.replace(snapshot.book);
+        book.raw_bids = snapshot.raw_bids;
+        book.raw_asks = snapshot.raw_asks;
+        return Ok(());
+    }
+    for update in &message.data {
+        let depth_update = update.depth_update(&message.arg);
+        let applied =
+            depth_update.continuity::<Consecutive>(book.book.update_id) == Continuity::Continuous;
+        apply_in_sequence::<Consecutive>(&mut book.book, &depth_update)?;
+        if applied {
+            book.record(update);
+        }
+        verify(book, update.checksum)?;
+    }
+    Ok(())
+}
+
+fn verify(book: &OkxBook, checksum: Option<i32>) -> Result<(), Error> {
+    if let Some(expected) = checksum {
+        let computed = self::checksum(book);
+        if computed != expected {
+            return Err(Error::ChecksumMismatch {
+                expected: expected as u32,
+                computed: computed as u32,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Signed CRC32 of `bid:size:ask:size:...` over the top 25 levels of each side, best first, interleaved.
+/// Once a side runs out only the other one is hashed. Levels without strings, which OKX didn't send, are hashed with
+/// the shortest representation of their doubles.
+pub fn checksum(book: &OkxBook) -> i32 {
+    let mut bids = book.book.bids().iter().rev().take(CHECKSUM_LEVELS);
+    let mut asks = book.book.asks().iter().rev().take(CHECKSUM_LEVELS);
+    let mut levels = Vec::with_capacity(CHECKSUM_LEVELS * 4);
+    loop {
+        let (bid, ask) = (bids.next(), asks.next());
+        if bid.is_none() && ask.is_none() {
+            break;
+        }
+        let bid = bid.map(|level| (level, &book.raw_bids));
+        let ask = ask.map(|level| (level, &book.raw_asks));
+        for (level, raw) in bid.into_iter().chain(ask) {
+            match raw.get(&level.0.to_bits()) {
+                Some(raw) => {
+                    levels.push(Cow::Borrowed(raw.price.as_str()));
+                    levels.push(Cow::Borrowed(raw.size.as_str()));
+                }
+                None => {
+                    levels.push(Cow::Owned(level.0.to_string()));
+                    levels.push(Cow::Owned(level.1.to_string()));
+                }
+            }
+        }
+    }
+    crc32fast::hash(levels.join(":").as_bytes()) as i32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SNAPSHOT: &str = r#"{
+        "arg": {"channel": "books", "instId": "BTC-USDT"},
+        "action": "snapshot",
+        "data": [{
+            "asks": [["8476.98", "415", "0", "13"]],
+            "bids": [["8476.97", "256", "0", "12"], ["8476.96", "100", "0", "1"]],
+            "ts": "1597026383085",
+            "checksum": -987723316,
+            "prevSeqId": -1,
+            "seqId": 123456
+        }]
+    }"#;
+
+    fn update(prev_seq_id: i64, checksum: i32) -> String {
+        format!(
+            r#"{{
+                "arg": {{"channel": "books", "instId": "BTC-USDT"}},
+                "action": "update",
+                "data": [{{
+                    "asks": [],
+                    "bids": [["8476.97", "200", "0", "11"]],
+                    "ts": "1597026383185",
+                    "checksum": {checksum},
+                    "prevSeqId": {prev_seq_id},
+                    "seqId": 123457
+                }}]
+            }}"#
+        )
+    }
+
+    fn snapshot() -> OkxBook {
+        book(&serde_json::from_str(SNAPSHOT).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn snapshot_then_update() {
+        let mut book = snapshot();
+        assert_eq!(book.book().update_id, 123456);
+
+        let message: BooksMessage = serde_json::from_str(&update(123456, 1668470051)).unwrap();
+        let mut normalized = snapshot().into_book();
+        for update in message.market_updates(0) {
+            normalized.apply_market_update(&update);
+        }
+        apply(&mut book, &message).unwrap();
+        assert_eq!(normalized, *book.book());
+        assert_eq!(book.book().update_id, 123457);
+        assert_eq!(
+            **book.book().bids(),
+            [
+                PriceAndQuantity(8476.96, 100.),
+                PriceAndQuantity(8476.97, 200.)
+            ]
+        );
+    }
+
+    #[test]
+    fn sequence_gap() {
+        let mut book = snapshot();
+        let message = serde_json::from_str(&update(123458, 1668470051)).unwrap();
+        assert_eq!(
+            apply(&mut book, &message),
+            Err(Error::SequenceGap {
+                last_book_id: 123456,
+                first_update_id: 123459,
+            })
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut book = snapshot();
+        let message = serde_json::from_str(&update(123456, 1)).unwrap();
+        assert!(apply(&mut book, &message).unwrap_err().requires_resync());
+    }
+
+    #[test]
+    fn checksum_hashes_strings_as_sent() {
+        let hash = |levels: &str| crc32fast::hash(levels.as_bytes()) as i32;
+        let snapshot = SNAPSHOT.replace(r#""415""#, r#""415.0""#).replace(
+            "-987723316",
+            &hash("8476.97:256:8476.98:415.0:8476.96:100").to_string(),
+        );
+        let mut book = book(&serde_json::from_str(&snapshot).unwrap()).unwrap();
+
+        // Updated levels hash their new strings, removed ones are gone.
+        let message = update(123456, hash("8476.97:200.00:8476.99:1.50:8476.96:100"))
+            .replace(r#""200""#, r#""200.00""#)
+            .replace(
+                r#""asks": []"#,
+                r#""asks": [["8476.98", "0", "0", "0"], ["8476.99", "1.50", "0", "1"]]"#,
+            );
+        apply(&mut book, &serde_json::from_str(&message).unwrap()).unwrap();
+        assert_eq!(**book.book().asks(), [PriceAndQuantity(8476.99, 1.5)]);
+    }
+}
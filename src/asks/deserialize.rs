@@ -1,13 +1,12 @@
 use super::AggregateOrCreate;
 use super::Asks;
-use super::PriceAndQuantity;
-use super::Update;
-use serde::{Deserialize, Deserializer as DeserializerT};
+use crate::ops::seed::LevelsSeed;
+use serde::{de::DeserializeSeed, Deserialize, Deserializer as DeserializerT};
 use std::fmt::Display;
 use std::ops::Add;
 use std::str::FromStr;
 
-/// Naive deserialization, consider implementing a visitor to stream json values instead of deseralizing into a vec and draining into the output type.
+/// Streams the levels into the side as they are visited, see [LevelsSeed].
 /// It uses the [AggregateOrCreate](super::AggregateOrCreate) strategy to fill the vec.
 impl<'de, P, Q> Deserialize<'de> for Asks<P, Q>
 where
@@ -21,11 +20,8 @@ where
     where
         D: DeserializerT<'de>,
     {
-        let mut prices: Vec<PriceAndQuantity<P, Q>> = Deserialize::deserialize(deserializer)?;
         let mut asks = Asks::new();
-        for i in prices.drain(..) {
-            Update::<AggregateOrCreate>::process(&mut asks, i)
-        }
+        LevelsSeed::<_, AggregateOrCreate>::new(&mut asks).deserialize(deserializer)?;
         Ok(asks)
     }
 }
@@ -33,6 +29,7 @@ where
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::PriceAndQuantity;
 
     #[test]
     fn deserialize() {
@@ -8,12 +8,11 @@ use super::{
     PriceAndQuantity,
 };
 #[cfg(feature = "serde")]
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::fmt::Display;
 use std::ops::{Add, Deref, DerefMut};
 
 #[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
-#[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct Asks<P = f64, Q = f64>(Vec<PriceAndQuantity<P, Q>>);
 
@@ -42,6 +41,20 @@ where
     }
 }
 
+/// Best level first, the order the exchange sends them in.
+#[cfg(feature = "serde")]
+impl<P, Q> Serialize for Asks<P, Q>
+where
+    PriceAndQuantity<P, Q>: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().rev())
+    }
+}
+
 impl<P, Q> Deref for Asks<P, Q> {
     type Target = Vec<PriceAndQuantity<P, Q>>;
 
@@ -1,14 +1,12 @@
 use super::Bids;
-use super::PriceAndQuantity;
-use super::Update;
-use crate::ops::update_strategies::AggregateOrCreate;
-use serde::{Deserialize, Deserializer as DeserializerT};
+use crate::ops::{seed::LevelsSeed, update_strategies::AggregateOrCreate};
+use serde::{de::DeserializeSeed, Deserialize, Deserializer as DeserializerT};
 use std::fmt::Display;
 use std::ops::Add;
 use std::str::FromStr;
 
-/// Naive deserialization, consider implementing a visitor to stream json values instead of deseralizing into a vec and draining into the output type.
-/// It uses the [AggregateOrCreate](super::AggregateOrCreate) strategy to fill the vec.
+/// Streams the levels into the side as they are visited, see [LevelsSeed].
+/// It uses the [AggregateOrCreate] strategy to fill the vec.
 impl<'de, P, Q> Deserialize<'de> for Bids<P, Q>
 where
     P: Deserialize<'de> + FromStr,
@@ -22,11 +20,8 @@ where
     where
         D: DeserializerT<'de>,
     {
-        let mut prices: Vec<PriceAndQuantity<P, Q>> = Deserialize::deserialize(deserializer)?;
         let mut bids = Bids::new();
-        for i in prices.drain(..) {
-            Update::<AggregateOrCreate>::process(&mut bids, i)
-        }
+        LevelsSeed::<_, AggregateOrCreate>::new(&mut bids).deserialize(deserializer)?;
         Ok(bids)
     }
 }
@@ -34,6 +29,7 @@ where
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::PriceAndQuantity;
 
     #[test]
     fn deserialize() {
@@ -5,14 +5,13 @@ use crate::ops::{PartitionPredicate, Strategy};
 
 use super::{ops::Update, PriceAndQuantity};
 #[cfg(feature = "serde")]
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::{
     fmt::Display,
     ops::{Add, Deref, DerefMut},
 };
 
 #[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
-#[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct Bids<P = f64, Q = f64>(Vec<PriceAndQuantity<P, Q>>);
 
@@ -41,6 +40,20 @@ impl<P, Q> From<Vec<PriceAndQuantity<P, Q>>> for Bids<P, Q> {
     }
 }
 
+/// Best level first, the order the exchange sends them in.
+#[cfg(feature = "serde")]
+impl<P, Q> Serialize for Bids<P, Q>
+where
+    PriceAndQuantity<P, Q>: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().rev())
+    }
+}
+
 impl<P, Q> Deref for Bids<P, Q> {
     type Target = Vec<PriceAndQuantity<P, Q>>;
 
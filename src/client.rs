@@ -0,0 +1,279 @@
+//! Client of the [crate::server] book service speaking native types.
+
+use crate::limit_order_book::protos::{
+    book_event::Event, limit_order_book_service_client::LimitOrderBookServiceClient, BookEvent,
+    BookRequest, LevelEncoding, Pair,
+};
+use crate::limit_order_book::sequence::{Continuity, Market};
+use crate::LimitOrderBook;
+use std::fmt::Display;
+use tonic::codegen::StdError;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Status, Streaming};
+
+#[derive(Debug)]
+pub enum ClientError {
+    Transport(tonic::transport::Error),
+    /// The server failed the call or the subscription.
+    Status(Box<Status>),
+    /// The server ended the subscription.
+    Ended,
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Transport(error) => write!(f, "transport error: {error}"),
+            ClientError::Status(status) => write!(f, "{status}"),
+            ClientError::Ended => write!(f, "subscription ended"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<tonic::transport::Error> for ClientError {
+    fn from(error: tonic::transport::Error) -> Self {
+        ClientError::Transport(error)
+    }
+}
+
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        ClientError::Status(Box::new(status))
+    }
+}
+
+/// Connection to a `LimitOrderBookService`. Levels are requested in [LevelEncoding::Scaled] so that they decode to
+/// the same doubles as the exchange's decimals, see [BookClient::with_encoding].
+#[derive(Clone, Debug)]
+pub struct BookClient {
+    inner: LimitOrderBookServiceClient<Channel>,
+    encoding: LevelEncoding,
+}
+
+impl BookClient {
+    /// Connects to `endpoint`, e.g. `"http://127.0.0.1:50051"`.
+    pub async fn connect<D>(endpoint: D) -> Result<Self, ClientError>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<StdError>,
+    {
+        Ok(Self::new(Endpoint::new(endpoint)?.connect().await?))
+    }
+
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            inner: LimitOrderBookServiceClient::new(channel),
+            encoding: LevelEncoding::Scaled,
+        }
+    }
+
+    /// Encoding of the levels the server sends, the native types are the same either way.
+    pub fn with_encoding(mut self, encoding: LevelEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The whole book of `pair`.
+    pub async fn book(&mut self, pair: &str) -> Result<LimitOrderBook, ClientError> {
+        let request = BookRequest {
+            pair: pair.to_owned(),
+            encoding: self.encoding.into(),
+            ..Default::default()
+        };
+        Ok(self
+            .inner
+            .get_limit_order_book(request)
+            .await?
+            .into_inner()
+            .into())
+    }
+
+    /// Subscribes to the book of `pair`, returns once the replica got its first snapshot.
+    /// Updates are sequenced by the rule of `market`.
+    pub async fn replicate(
+        &self,
+        pair: impl Into<String>,
+        market: Market,
+    ) -> Result<BookReplica, ClientError> {
+        let mut client = self.inner.clone();
+        let request = Pair {
+            pair: pair.into(),
+            encoding: self.encoding.into(),
+        };
+        let (events, book) = subscribe(&mut client, request.clone()).await?;
+        Ok(BookReplica {
+            client,
+            request,
+            market,
+            events,
+            book,
+        })
+    }
+}
+
+/// Subscribes, waiting for the snapshot the subscription starts with.
+async fn subscribe(
+    client: &mut LimitOrderBookServiceClient<Channel>,
+    request: Pair,
+) -> Result<(Streaming<BookEvent>, LimitOrderBook), ClientError> {
+    let mut events = client
+        .subscribe_limit_order_book(request)
+        .await?
+        .into_inner();
+    loop {
+        match events.message().await?.ok_or(ClientError::Ended)?.event {
+            Some(Event::Snapshot(book)) => return Ok((events, book.into())),
+            _ => continue,
+        }
+    }
+}
+
+/// How the replica changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Replicated {
+    /// The book was replaced, either by the server or after a gap.
+    Snapshot,
+    Update,
+}
+
+/// Local copy of a served book, kept up to date by [BookReplica::next].
+#[derive(Debug)]
+pub struct BookReplica {
+    client: LimitOrderBookServiceClient<Channel>,
+    request: Pair,
+    market: Market,
+    events: Streaming<BookEvent>,
+    book: LimitOrderBook,
+}
+
+impl BookReplica {
+    pub fn pair(&self) -> &str {
+        &self.request.pair
+    }
+
+    pub fn book(&self) -> &LimitOrderBook {
+        &self.book
+    }
+
+    pub fn into_book(self) -> LimitOrderBook {
+        self.book
+    }
+
+    /// Waits for the next change of the book and applies it. An update that doesn't follow the replica, e.g. after the
+    /// server dropped some, makes it subscribe again and the change is the new snapshot.
+    pub async fn next(&mut self) -> Result<Replicated, ClientError> {
+        loop {
+            let event = self.events.message().await?.ok_or(ClientError::Ended)?;
+            let update = match event.event {
+                Some(Event::Snapshot(book)) => {
+                    self.book = book.into();
+                    return Ok(Replicated::Snapshot);
+                }
+                Some(Event::Update(update)) => update.into(),
+                None => continue,
+            };
+            match self.market.continuity(&update, self.book.update_id) {
+                Continuity::Continuous => {
+                    self.book.apply(&update);
+                    return Ok(Replicated::Update);
+                }
+                Continuity::Stale => continue,
+                Continuity::Gap => {
+                    (self.events, self.book) =
+                        subscribe(&mut self.client, self.request.clone()).await?;
+                    return Ok(Replicated::Snapshot);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::test::channel;
+    use crate::server::BookStore;
+    use crate::{DepthUpdate, PriceAndQuantity};
+
+    fn update(update_id: u64, bid: f64) -> DepthUpdate {
+        DepthUpdate {
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: vec![PriceAndQuantity(bid, 1.)].into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn replicates_and_resnapshots_on_gaps() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        store
+            .snapshot(
+                "BTCUSDT",
+                LimitOrderBook::from_sides(1, vec![].into(), vec![].into()),
+            )
+            .unwrap();
+        let mut client = BookClient::new(channel(store.clone()).await);
+        assert!(matches!(
+            client.book("DOGEUSDT").await,
+            Err(ClientError::Status(status)) if status.code() == tonic::Code::NotFound
+        ));
+
+        let mut replica = client.replicate("BTCUSDT", Market::Spot).await.unwrap();
+        assert_eq!(replica.pair(), "BTCUSDT");
+        assert_eq!(replica.book().update_id, 1);
+        for (update_id, bid) in [(2, 1.), (3, 2.)] {
+            store.apply_to("BTCUSDT", update(update_id, bid)).unwrap();
+            assert_eq!(replica.next().await.unwrap(), Replicated::Update);
+        }
+        assert_eq!(*replica.book(), client.book("BTCUSDT").await.unwrap());
+
+        // Rewinds the replica as if it had missed updates, the next one can't be applied.
+        replica.book.update_id = 1;
+        store.apply_to("BTCUSDT", update(4, 3.)).unwrap();
+        assert_eq!(replica.next().await.unwrap(), Replicated::Snapshot);
+        assert_eq!(replica.book().update_id, 4);
+        assert_eq!(*replica.book(), *store.read().book("BTCUSDT").unwrap());
+
+        store.apply_to("BTCUSDT", update(5, 4.)).unwrap();
+        assert_eq!(replica.next().await.unwrap(), Replicated::Update);
+        store.remove("BTCUSDT");
+        assert!(matches!(
+            replica.next().await,
+            Err(ClientError::Status(status)) if status.code() == tonic::Code::NotFound
+        ));
+        assert_eq!(replica.into_book().update_id, 5);
+    }
+
+    #[tokio::test]
+    async fn replicates_futures_by_previous_update_id() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::UsdMFutures);
+        store
+            .snapshot(
+                "BTCUSDT",
+                LimitOrderBook::from_sides(10, vec![].into(), vec![].into()),
+            )
+            .unwrap();
+        let client = BookClient::new(channel(store.clone()).await);
+        let mut replica = client
+            .replicate("BTCUSDT", Market::UsdMFutures)
+            .await
+            .unwrap();
+
+        // Chains on the previous update's last id, the ranges don't overlap.
+        let update = DepthUpdate {
+            first_update_id: 15,
+            last_update_id: 20,
+            previous_update_id: Some(10),
+            bids: vec![PriceAndQuantity(1., 1.)].into(),
+            ..Default::default()
+        };
+        store.apply_to("BTCUSDT", update).unwrap();
+        assert_eq!(replica.next().await.unwrap(), Replicated::Update);
+        assert_eq!(*replica.book(), *store.read().book("BTCUSDT").unwrap());
+    }
+}
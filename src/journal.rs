@@ -0,0 +1,475 @@
+//! Append-only journal of the [DepthUpdate]s received, for replay and incident analysis.
+//!
+//! The journal is a directory of segments named after their index, e.g. `00000000000000000001.journal`. A segment
+//! starts with [MAGIC] and its format version, then holds frames: the payload length and its CRC32 as little endian
+//! `u32`s, then the payload, the receive time as a SCALE `u64` followed by the update in its [Versioned] envelope.
+//!
+//! The writer never appends to an existing segment, after a restart it starts a new one. The tail of any segment may
+//! thus be a record torn by a crash, the reader drops it and carries on with the next segment.
+
+use crate::limit_order_book::Versioned;
+use crate::{Decode, DepthUpdate, Encode};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// First bytes of a segment.
+pub const MAGIC: &[u8; 4] = b"LOBJ";
+
+const FORMAT_VERSION: u8 = 1;
+
+const SEGMENT_HEADER_LEN: usize = MAGIC.len() + 1;
+
+const FRAME_HEADER_LEN: usize = 8;
+
+const EXTENSION: &str = "journal";
+
+/// Past this, the writer moves on to a new segment.
+pub const DEFAULT_MAX_SEGMENT_BYTES: u64 = 64 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalRecord {
+    /// Milliseconds since the epoch.
+    pub receive_time: u64,
+    pub update: DepthUpdate,
+}
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    /// A record failed its CRC or didn't decode, and isn't the tail of its segment.
+    Corrupt {
+        segment: PathBuf,
+        offset: u64,
+    },
+    NotASegment(PathBuf),
+}
+
+impl Display for JournalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JournalError::Io(error) => write!(f, "journal io error: {error}"),
+            JournalError::Corrupt { segment, offset } => {
+                write!(f, "corrupt record in {} at {offset}", segment.display())
+            }
+            JournalError::NotASegment(path) => {
+                write!(f, "{} is not a journal segment", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::Io(error)
+    }
+}
+
+/// Segments of `dir` ordered by index.
+fn segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            let index = path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse().ok());
+            if let Some(index) = index {
+                segments.push((index, path));
+            }
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+fn frame(receive_time: u64, update: &DepthUpdate) -> Vec<u8> {
+    let mut payload = receive_time.encode();
+    payload.extend(update.encode_versioned());
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+struct Segment {
+    file: BufWriter<File>,
+    bytes: u64,
+    /// Receive time of its first record.
+    start: u64,
+}
+
+/// Appends records to the journal of a directory, rotating segments by size and optionally by age.
+pub struct JournalWriter {
+    dir: PathBuf,
+    max_segment_bytes: u64,
+    max_segment_age: Option<u64>,
+    next_index: u64,
+    segment: Option<Segment>,
+}
+
+impl JournalWriter {
+    /// Creates `dir` if needed, the first record goes to a new segment after the existing ones.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let next_index = segments(&dir)?.last().map_or(1, |(index, _)| index + 1);
+        Ok(Self {
+            dir,
+            max_segment_bytes: DEFAULT_MAX_SEGMENT_BYTES,
+            max_segment_age: None,
+            next_index,
+            segment: None,
+        })
+    }
+
+    /// A segment holds at least one record, however large.
+    pub fn with_max_segment_bytes(mut self, bytes: u64) -> Self {
+        self.max_segment_bytes = bytes;
+        self
+    }
+
+    /// Milliseconds of receive time a segment spans at most.
+    pub fn with_max_segment_age(mut self, millis: u64) -> Self {
+        self.max_segment_age = Some(millis);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Buffers the record, see [JournalWriter::flush] to make it durable.
+    pub fn append(&mut self, receive_time: u64, update: &DepthUpdate) -> io::Result<()> {
+        let frame = frame(receive_time, update);
+        let full = self.segment.as_ref().is_some_and(|segment| {
+            segment.bytes + frame.len() as u64 > self.max_segment_bytes
+                || self
+                    .max_segment_age
+                    .is_some_and(|age| receive_time.saturating_sub(segment.start) >= age)
+        });
+        if full {
+            self.close_segment()?;
+        }
+        if self.segment.is_none() {
+            self.segment = Some(self.create_segment(receive_time)?);
+        }
+        let segment = self.segment.as_mut().unwrap();
+        segment.file.write_all(&frame)?;
+        segment.bytes += frame.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the buffered records and syncs them to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(segment) = &mut self.segment {
+            segment.file.flush()?;
+            segment.file.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn create_segment(&mut self, start: u64) -> io::Result<Segment> {
+        let path = self
+            .dir
+            .join(format!("{:020}.{EXTENSION}", self.next_index));
+        let mut file = BufWriter::new(File::create_new(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&[FORMAT_VERSION])?;
+        self.next_index += 1;
+        Ok(Segment {
+            file,
+            bytes: SEGMENT_HEADER_LEN as u64,
+            start,
+        })
+    }
+
+    fn close_segment(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.segment = None;
+        Ok(())
+    }
+}
+
+/// Reads back the records of a journal in the order they were appended.
+#[derive(Debug)]
+pub struct JournalReader {
+    segments: std::vec::IntoIter<(u64, PathBuf)>,
+    /// Current segment, its path, content and the offset of the next frame.
+    current: Option<(PathBuf, Vec<u8>, usize)>,
+    truncated: Vec<PathBuf>,
+    failed: bool,
+}
+
+impl JournalReader {
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            segments: segments(dir.as_ref())?.into_iter(),
+            current: None,
+            truncated: Vec::new(),
+            failed: false,
+        })
+    }
+
+    /// Segments read so far whose torn final record was dropped.
+    pub fn truncated(&self) -> &[PathBuf] {
+        &self.truncated
+    }
+
+    /// The next record of the current segment, `None` at its end.
+    fn next_in_segment(&mut self) -> Option<Result<JournalRecord, JournalError>> {
+        let (path, content, offset) = self.current.as_mut()?;
+        let rest = &content[*offset..];
+        if rest.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let frame = rest.get(..FRAME_HEADER_LEN).and_then(|header| {
+            let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+            let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+            Some((crc, rest.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)?))
+        });
+        let record = frame.and_then(|(crc, payload)| {
+            (crc32fast::hash(payload) == crc)
+                .then(|| decode(payload))
+                .flatten()
+                .map(|record| (record, FRAME_HEADER_LEN + payload.len()))
+        });
+        match (record, frame) {
+            (Some((record, len)), _) => {
+                *offset += len;
+                Some(Ok(record))
+            }
+            // A torn write may also have extended the file before the payload made it to disk, leaving zeros. An
+            // all-zero header reads as an empty frame with a matching CRC, it is torn as well.
+            (None, None) => self.torn(),
+            (None, Some((_, payload)))
+                if rest[FRAME_HEADER_LEN + payload.len()..]
+                    .iter()
+                    .all(|&byte| byte == 0) =>
+            {
+                self.torn()
+            }
+            (None, Some(_)) => Some(Err(JournalError::Corrupt {
+                segment: path.clone(),
+                offset: *offset as u64,
+            })),
+        }
+    }
+
+    /// Drops the rest of the current segment.
+    fn torn<T>(&mut self) -> Option<T> {
+        let (path, _, _) = self.current.take()?;
+        self.truncated.push(path);
+        None
+    }
+
+    fn open_next_segment(&mut self) -> Option<Result<(), JournalError>> {
+        let (_, path) = self.segments.next()?;
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(error) => return Some(Err(error.into())),
+        };
+        let header = [&MAGIC[..], &[FORMAT_VERSION]].concat();
+        if content.len() < SEGMENT_HEADER_LEN && header.starts_with(&content) {
+            // A crash right after creating the segment leaves it without a complete header.
+            self.truncated.push(path);
+        } else if content.starts_with(&header) {
+            self.current = Some((path, content, SEGMENT_HEADER_LEN));
+        } else {
+            return Some(Err(JournalError::NotASegment(path)));
+        }
+        Some(Ok(()))
+    }
+}
+
+fn decode(mut payload: &[u8]) -> Option<JournalRecord> {
+    let receive_time = u64::decode(&mut payload).ok()?;
+    let update = DepthUpdate::decode_versioned(payload).ok()?;
+    Some(JournalRecord {
+        receive_time,
+        update,
+    })
+}
+
+/// Stops at the first error.
+impl Iterator for JournalReader {
+    type Item = Result<JournalRecord, JournalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            if let Some(record) = self.next_in_segment() {
+                self.failed = record.is_err();
+                return Some(record);
+            }
+            if let Err(error) = self.open_next_segment()? {
+                self.failed = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PriceAndQuantity;
+
+    /// An empty directory for the test, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("lob-journal-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn update(update_id: u64) -> DepthUpdate {
+        DepthUpdate {
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: vec![PriceAndQuantity(update_id as f64, 1.)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn read(dir: &Path) -> (Vec<u64>, JournalReader) {
+        let mut reader = JournalReader::open(dir).unwrap();
+        let ids = reader
+            .by_ref()
+            .map(|record| record.unwrap().update.last_update_id)
+            .collect();
+        (ids, reader)
+    }
+
+    fn write(dir: &Path, writer: JournalWriter, records: impl IntoIterator<Item = u64>) {
+        let mut writer = writer;
+        assert_eq!(writer.dir(), dir);
+        for update_id in records {
+            writer
+                .append(1_000 + update_id, &update(update_id))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn rotates_and_reads_back() {
+        let dir = TestDir::new("rotates");
+        let frame_len = frame(0, &update(1)).len() as u64;
+        let writer = JournalWriter::open(&dir.0)
+            .unwrap()
+            .with_max_segment_bytes(SEGMENT_HEADER_LEN as u64 + 2 * frame_len);
+        write(&dir.0, writer, 1..=5);
+        assert_eq!(segments(&dir.0).unwrap().len(), 3);
+
+        let mut reader = JournalReader::open(&dir.0).unwrap();
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(
+            first,
+            JournalRecord {
+                receive_time: 1_001,
+                update: update(1)
+            }
+        );
+        assert_eq!(reader.count(), 4);
+
+        // Reopening starts a new segment, by age this time.
+        let writer = JournalWriter::open(&dir.0).unwrap().with_max_segment_age(2);
+        write(&dir.0, writer, 6..=9);
+        let segments = segments(&dir.0).unwrap();
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[3].0, 4);
+        let (ids, reader) = read(&dir.0);
+        assert_eq!(ids, (1..=9).collect::<Vec<_>>());
+        assert!(reader.truncated().is_empty());
+    }
+
+    #[test]
+    fn drops_torn_final_records() {
+        let dir = TestDir::new("torn");
+        write(&dir.0, JournalWriter::open(&dir.0).unwrap(), 1..=3);
+        let (_, path) = segments(&dir.0).unwrap().pop().unwrap();
+        let content = fs::read(&path).unwrap();
+
+        // Cut in the last payload, then in the last frame header.
+        for cut in [3, frame(0, &update(3)).len() - 2] {
+            fs::write(&path, &content[..content.len() - cut]).unwrap();
+            let (ids, reader) = read(&dir.0);
+            assert_eq!(ids, [1, 2]);
+            assert_eq!(reader.truncated(), [path.as_path()]);
+        }
+
+        // Whole zero-filled blocks past the last record, their first 8 bytes read as an empty frame.
+        let mut extended = content.clone();
+        extended.resize(content.len() + 4096, 0);
+        fs::write(&path, &extended).unwrap();
+        let (ids, reader) = read(&dir.0);
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(reader.truncated(), [path.as_path()]);
+
+        // Zeroed tail, as a crash may leave the file extended without the data.
+        let mut zeroed = content.clone();
+        let len = zeroed.len();
+        zeroed[len - 4..].fill(0);
+        fs::write(&path, &zeroed).unwrap();
+        let (ids, _) = read(&dir.0);
+        assert_eq!(ids, [1, 2]);
+
+        // Appending after the crash goes to a new segment, the torn one is still read up to its tail.
+        write(&dir.0, JournalWriter::open(&dir.0).unwrap(), 4..=4);
+        let (ids, reader) = read(&dir.0);
+        assert_eq!(ids, [1, 2, 4]);
+        assert_eq!(reader.truncated(), [path.as_path()]);
+
+        // A segment torn in its header holds nothing.
+        fs::write(&path, &MAGIC[..2]).unwrap();
+        let (ids, _) = read(&dir.0);
+        assert_eq!(ids, [4]);
+    }
+
+    #[test]
+    fn reports_corruption() {
+        let dir = TestDir::new("corrupt");
+        write(&dir.0, JournalWriter::open(&dir.0).unwrap(), 1..=3);
+        let (_, path) = segments(&dir.0).unwrap().pop().unwrap();
+        let mut content = fs::read(&path).unwrap();
+        let second = SEGMENT_HEADER_LEN + frame(0, &update(1)).len();
+        content[second + FRAME_HEADER_LEN] ^= 0xff;
+        fs::write(&path, &content).unwrap();
+
+        let mut reader = JournalReader::open(&dir.0).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().update, update(1));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(JournalError::Corrupt { offset, .. })) if offset == second as u64
+        ));
+        assert!(reader.next().is_none());
+
+        fs::write(&path, b"not a journal").unwrap();
+        let mut reader = JournalReader::open(&dir.0).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(JournalError::NotASegment(_)))
+        ));
+    }
+}
@@ -1,17 +1,27 @@
+#[cfg(feature = "serde")]
+pub mod adapters;
 pub mod asks;
 pub mod bids;
+#[cfg(feature = "grpc")]
+pub mod client;
+#[cfg(feature = "codec")]
+pub mod journal;
 pub mod limit_order_book;
+pub mod matching;
 pub mod ops;
 pub mod price_and_quantity;
+#[cfg(feature = "grpc")]
+pub mod server;
+pub mod soa;
 
 pub use asks::Asks;
 pub use bids::Bids;
 #[cfg(feature = "codec")]
 pub use codec::{Decode, Encode};
-pub use limit_order_book::{DepthUpdate, LimitOrderBook};
+pub use limit_order_book::{BookTicker, DepthUpdate, LimitOrderBook, PartialDepth};
 pub use price_and_quantity::PriceAndQuantity;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OrderType {
     Buy,
     Sell,
@@ -22,3 +32,20 @@ pub struct Order<P, Q> {
     price_quantity: PriceAndQuantity<P, Q>,
     order_type: OrderType,
 }
+
+impl<P, Q> Order<P, Q> {
+    pub fn new(price_quantity: PriceAndQuantity<P, Q>, order_type: OrderType) -> Self {
+        Self {
+            price_quantity,
+            order_type,
+        }
+    }
+
+    pub fn price_quantity(&self) -> &PriceAndQuantity<P, Q> {
+        &self.price_quantity
+    }
+
+    pub fn order_type(&self) -> OrderType {
+        self.order_type
+    }
+}
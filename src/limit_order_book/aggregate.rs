@@ -0,0 +1,122 @@
+use super::LimitOrderBook;
+use crate::PriceAndQuantity;
+
+/// Best bid and ask of a book, either is `None` when its side is empty.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct TopOfBook {
+    pub best_bid: Option<PriceAndQuantity<f64, f64>>,
+    pub best_ask: Option<PriceAndQuantity<f64, f64>>,
+}
+
+impl TopOfBook {
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask?.0 - self.best_bid?.0)
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_ask?.0 + self.best_bid?.0) / 2.)
+    }
+}
+
+/// Number of `tick`s in `price`, rounded with `round` unless `price` already is a multiple of `tick` give or take
+/// the floating point error of the division.
+fn ticks(price: f64, tick: f64, round: fn(f64) -> f64) -> f64 {
+    let ticks = price / tick;
+    let nearest = ticks.round();
+    if (ticks - nearest).abs() <= 1e-9 * nearest.abs().max(1.) {
+        nearest
+    } else {
+        round(ticks)
+    }
+}
+
+/// Sums the quantity of consecutive levels falling in the same bucket, the side's order is kept as `round` is monotonic.
+fn group(
+    levels: &[PriceAndQuantity<f64, f64>],
+    tick: f64,
+    round: fn(f64) -> f64,
+) -> Vec<PriceAndQuantity<f64, f64>> {
+    let mut grouped: Vec<PriceAndQuantity<f64, f64>> = Vec::with_capacity(levels.len());
+    for &PriceAndQuantity(price, quantity) in levels {
+        let price = ticks(price, tick, round) * tick;
+        match grouped.last_mut() {
+            Some(last) if last.0 == price => last.1 += quantity,
+            _ => grouped.push(PriceAndQuantity(price, quantity)),
+        }
+    }
+    grouped
+}
+
+impl LimitOrderBook {
+    pub fn top_of_book(&self) -> TopOfBook {
+        TopOfBook {
+            best_bid: self.bids.last().copied(),
+            best_ask: self.asks.last().copied(),
+        }
+    }
+
+    /// The book with its levels grouped by multiples of `tick`, bids are rounded down and asks up so that the
+    /// grouped book never looks tighter than it is. `tick` must be positive.
+    pub fn grouped(&self, tick: f64) -> LimitOrderBook {
+        debug_assert!(tick > 0., "tick must be positive");
+        LimitOrderBook::from_sides(
+            self.update_id,
+            group(&self.bids, tick, f64::floor).into(),
+            group(&self.asks, tick, f64::ceil).into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn book() -> LimitOrderBook {
+        LimitOrderBook::from_sides(
+            3,
+            vec![
+                PriceAndQuantity(98.5, 1.),
+                PriceAndQuantity(99., 2.),
+                PriceAndQuantity(99.75, 3.),
+            ]
+            .into(),
+            vec![
+                PriceAndQuantity(101.5, 4.),
+                PriceAndQuantity(100.5, 5.),
+                PriceAndQuantity(100.25, 6.),
+            ]
+            .into(),
+        )
+    }
+
+    #[test]
+    fn top_of_book() {
+        let top = book().top_of_book();
+        assert_eq!(top.best_bid, Some(PriceAndQuantity(99.75, 3.)));
+        assert_eq!(top.best_ask, Some(PriceAndQuantity(100.25, 6.)));
+        assert_eq!(top.spread(), Some(0.5));
+        assert_eq!(top.mid(), Some(100.));
+
+        let top = LimitOrderBook::new().top_of_book();
+        assert_eq!(top, TopOfBook::default());
+        assert_eq!(top.spread(), None);
+        assert_eq!(top.mid(), None);
+    }
+
+    #[test]
+    fn grouped() {
+        let grouped = book().grouped(1.);
+        assert_eq!(grouped.update_id, 3);
+        assert_eq!(
+            **grouped.bids(),
+            [PriceAndQuantity(98., 1.), PriceAndQuantity(99., 5.)]
+        );
+        assert_eq!(
+            **grouped.asks(),
+            [PriceAndQuantity(102., 4.), PriceAndQuantity(101., 11.)]
+        );
+        // Prices already on the grid are kept despite the division's rounding error.
+        let grouped = book().grouped(0.25);
+        assert_eq!(grouped, book());
+    }
+}
@@ -0,0 +1,183 @@
+use super::LimitOrderBook;
+use std::ops::DerefMut;
+
+/// Caps every side of a [LimitOrderBook] to its best `levels`.
+/// Once a level has been evicted the side is incomplete beyond it: quantities past the boundary are unknown
+/// and updates for them are dropped.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct DepthLimit {
+    pub levels: usize,
+    /// Best evicted bid, bids at or below this price are not tracked.
+    pub bids_incomplete_below: Option<f64>,
+    /// Best evicted ask, asks at or above this price are not tracked.
+    pub asks_incomplete_above: Option<f64>,
+}
+
+impl DepthLimit {
+    pub fn new(levels: usize) -> Self {
+        Self {
+            levels,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.bids_incomplete_below.is_some() || self.asks_incomplete_above.is_some()
+    }
+
+    pub(super) fn tracks_bid(&self, price: f64) -> bool {
+        self.bids_incomplete_below.is_none_or(|below| below < price)
+    }
+
+    pub(super) fn tracks_ask(&self, price: f64) -> bool {
+        self.asks_incomplete_above.is_none_or(|above| price < above)
+    }
+}
+
+/// Drops the worst levels beyond `levels`, they are at the front of the side. Returns the best evicted price.
+fn evict<T>(side: &mut T, levels: usize) -> Option<f64>
+where
+    T: DerefMut<Target = Vec<crate::PriceAndQuantity<f64, f64>>>,
+{
+    let excess = side.len().saturating_sub(levels);
+    let boundary = excess.checked_sub(1).map(|i| side[i].0);
+    side.drain(..excess);
+    boundary
+}
+
+impl LimitOrderBook {
+    /// An empty book that keeps at most `levels` per side.
+    pub fn with_depth_limit(levels: usize) -> Self {
+        let mut book = Self::new();
+        book.set_depth_limit(levels);
+        book
+    }
+
+    /// Starts capping the book to `levels` per side, evicting any excess right away.
+    pub fn set_depth_limit(&mut self, levels: usize) {
+        self.depth_limit = Some(DepthLimit::new(levels));
+        self.enforce_depth_limit();
+    }
+
+    /// Replaces the book with `snapshot`. A limit set on this book carries over and is enforced on the snapshot.
+    pub fn replace(&mut self, snapshot: LimitOrderBook) {
+        let limit = self.depth_limit.take();
+        *self = snapshot;
+        if let Some(limit) = limit {
+            self.set_depth_limit(limit.levels);
+        }
+    }
+
+    pub fn depth_limit(&self) -> Option<&DepthLimit> {
+        self.depth_limit.as_ref()
+    }
+
+    /// True when levels were evicted and later removals left a side with fewer than the limit.
+    /// The missing levels can't be recovered from diffs, a new snapshot is required.
+    pub fn needs_resnapshot(&self) -> bool {
+        self.depth_limit.as_ref().is_some_and(|limit| {
+            (limit.bids_incomplete_below.is_some() && self.bids.len() < limit.levels)
+                || (limit.asks_incomplete_above.is_some() && self.asks.len() < limit.levels)
+        })
+    }
+
+    pub(super) fn enforce_depth_limit(&mut self) {
+        let Some(limit) = self.depth_limit.as_mut() else {
+            return;
+        };
+        if let Some(boundary) = evict(&mut self.bids, limit.levels) {
+            limit.bids_incomplete_below = Some(boundary);
+        }
+        if let Some(boundary) = evict(&mut self.asks, limit.levels) {
+            limit.asks_incomplete_above = Some(boundary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{DepthUpdate, LimitOrderBook, PriceAndQuantity};
+
+    #[test]
+    fn evicts_worst_levels() {
+        let mut book = LimitOrderBook::with_depth_limit(2);
+        for price in [1., 2., 3.] {
+            book.add_bid(PriceAndQuantity(price, 1.));
+            book.add_ask(PriceAndQuantity(price + 10., 1.));
+        }
+        assert_eq!(
+            *book.bids,
+            [PriceAndQuantity(2., 1.), PriceAndQuantity(3., 1.)]
+        );
+        assert_eq!(
+            *book.asks,
+            [PriceAndQuantity(12., 1.), PriceAndQuantity(11., 1.)]
+        );
+
+        let limit = book.depth_limit().unwrap();
+        assert_eq!(limit.bids_incomplete_below, Some(1.));
+        assert_eq!(limit.asks_incomplete_above, Some(13.));
+        assert!(!book.needs_resnapshot());
+
+        // Beyond the boundary, dropped.
+        book.add_bid(PriceAndQuantity(0.5, 1.));
+        assert_eq!(book.bids.len(), 2);
+    }
+
+    #[test]
+    fn removal_below_limit_needs_resnapshot() {
+        let mut book = LimitOrderBook::with_depth_limit(2);
+        book.apply(&DepthUpdate {
+            bids: vec![
+                PriceAndQuantity(1., 1.),
+                PriceAndQuantity(2., 1.),
+                PriceAndQuantity(3., 1.),
+            ]
+            .into(),
+            ..Default::default()
+        });
+        assert!(!book.needs_resnapshot());
+
+        book.apply(&DepthUpdate {
+            bids: vec![PriceAndQuantity(3., 0.)].into(),
+            ..Default::default()
+        });
+        assert_eq!(*book.bids, [PriceAndQuantity(2., 1.)]);
+        assert!(book.needs_resnapshot());
+    }
+
+    #[test]
+    fn replace_keeps_limit() {
+        let mut book = LimitOrderBook::with_depth_limit(2);
+        book.add_bid(PriceAndQuantity(1., 1.));
+        let snapshot = LimitOrderBook::from_sides(
+            7,
+            vec![
+                PriceAndQuantity(1., 1.),
+                PriceAndQuantity(2., 1.),
+                PriceAndQuantity(3., 1.),
+            ]
+            .into(),
+            Default::default(),
+        );
+        book.replace(snapshot);
+        assert_eq!(book.update_id, 7);
+        assert_eq!(
+            *book.bids,
+            [PriceAndQuantity(2., 1.), PriceAndQuantity(3., 1.)]
+        );
+        assert_eq!(book.depth_limit().unwrap().bids_incomplete_below, Some(1.));
+
+        let mut unlimited = LimitOrderBook::new();
+        unlimited.replace(book.clone());
+        assert_eq!(unlimited, book);
+    }
+
+    #[test]
+    fn thin_complete_book_is_fine() {
+        let mut book = LimitOrderBook::with_depth_limit(2);
+        book.add_bid(PriceAndQuantity(1., 1.));
+        book.add_bid(PriceAndQuantity(1., 0.));
+        assert!(!book.needs_resnapshot());
+    }
+}
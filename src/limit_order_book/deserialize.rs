@@ -1,10 +1,249 @@
+use super::sequence::{Continuity, Market};
+use super::{DepthUpdate, LimitOrderBook};
+use crate::ops::{update_strategies::ReplaceOrRemove, Update};
+use crate::{Asks, Bids, PriceAndQuantity};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Applies a depth update straight into `book` while it is being deserialized, the levels are never collected.
+///
+/// The update ids must precede the levels, as they do on the Binance streams, so the continuity check can run first.
+/// Updates are checked with the rule of `market`, one that isn't [Continuity::Continuous] is read but not applied and
+/// `book.update_id` stays unchanged.
+///
+/// The levels are applied as they are read, so an error past the first one, e.g. a malformed level, leaves the book
+/// with part of the update and its previous `update_id`. Such a book no longer matches the venue's: drop it and
+/// resynchronize from a snapshot.
+pub struct ApplyDepthUpdate<'a> {
+    pub book: &'a mut LimitOrderBook,
+    pub market: Market,
+}
+
+impl<'a> ApplyDepthUpdate<'a> {
+    pub fn new(book: &'a mut LimitOrderBook, market: Market) -> Self {
+        Self { book, market }
+    }
+}
+
+/// What [ApplyDepthUpdate] did with an update.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApplyOutcome {
+    /// The update without its levels.
+    pub update: DepthUpdate,
+    /// [Continuity::Continuous] if it was applied, otherwise why it was skipped.
+    pub continuity: Continuity,
+}
+
+impl ApplyOutcome {
+    pub fn is_applied(&self) -> bool {
+        self.continuity == Continuity::Continuous
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ApplyDepthUpdate<'_> {
+    type Value = ApplyOutcome;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+enum Field {
+    FirstUpdateId,
+    LastUpdateId,
+    PreviousUpdateId,
+    TransactionTime,
+    Bids,
+    Asks,
+    #[cfg(feature = "event-id")]
+    EventId,
+    #[cfg(feature = "event-time")]
+    EventTime,
+    #[cfg(feature = "event-symbol")]
+    EventSymbol,
+    Ignored,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FieldVisitor;
+
+        impl Visitor<'_> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a depth update field")
+            }
+
+            fn visit_str<E>(self, field: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(match field {
+                    "U" | "first_update_id" => Field::FirstUpdateId,
+                    "u" | "last_update_id" => Field::LastUpdateId,
+                    "pu" | "previous_update_id" => Field::PreviousUpdateId,
+                    "T" | "transaction_time" => Field::TransactionTime,
+                    "b" | "bids" => Field::Bids,
+                    "a" | "asks" => Field::Asks,
+                    #[cfg(feature = "event-id")]
+                    "e" | "id" => Field::EventId,
+                    #[cfg(feature = "event-time")]
+                    "E" | "time" => Field::EventTime,
+                    #[cfg(feature = "event-symbol")]
+                    "s" | "symbol" => Field::EventSymbol,
+                    _ => Field::Ignored,
+                })
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+impl<'de> Visitor<'de> for ApplyDepthUpdate<'_> {
+    type Value = ApplyOutcome;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a depth update")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let (book, market) = (self.book, self.market);
+        let mut first_update_id = None;
+        let mut last_update_id = None;
+        let mut previous_update_id = None;
+        let mut transaction_time = None;
+        // Decided on the first side, before any level is applied.
+        let mut checked = None;
+        #[cfg(feature = "event")]
+        #[allow(unused_mut)]
+        let mut event = super::Event::default();
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::FirstUpdateId => first_update_id = Some(map.next_value()?),
+                Field::LastUpdateId => last_update_id = Some(map.next_value()?),
+                Field::PreviousUpdateId => previous_update_id = map.next_value()?,
+                Field::TransactionTime => transaction_time = map.next_value()?,
+                Field::Bids | Field::Asks => {
+                    let (Some(first_update_id), Some(last_update_id)) =
+                        (first_update_id, last_update_id)
+                    else {
+                        return Err(de::Error::custom("`U` and `u` must precede the levels"));
+                    };
+                    let ids = DepthUpdate {
+                        first_update_id,
+                        last_update_id,
+                        previous_update_id,
+                        ..Default::default()
+                    };
+                    let continuity =
+                        *checked.get_or_insert_with(|| market.continuity(&ids, book.update_id));
+                    if continuity != Continuity::Continuous {
+                        map.next_value::<IgnoredAny>()?;
+                    } else {
+                        let bids = matches!(key, Field::Bids);
+                        map.next_value_seed(BookSide {
+                            book: &mut *book,
+                            bids,
+                        })?;
+                    }
+                }
+                #[cfg(feature = "event-id")]
+                Field::EventId => event.id = map.next_value()?,
+                #[cfg(feature = "event-time")]
+                Field::EventTime => event.time = map.next_value()?,
+                #[cfg(feature = "event-symbol")]
+                Field::EventSymbol => event.symbol = map.next_value()?,
+                Field::Ignored => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let update = DepthUpdate {
+            #[cfg(feature = "event")]
+            event,
+            first_update_id: first_update_id.ok_or_else(|| de::Error::missing_field("U"))?,
+            last_update_id: last_update_id.ok_or_else(|| de::Error::missing_field("u"))?,
+            previous_update_id,
+            transaction_time,
+            bids: Bids::new(),
+            asks: Asks::new(),
+        };
+        let continuity = checked.unwrap_or_else(|| market.continuity(&update, book.update_id));
+        if continuity == Continuity::Continuous {
+            book.enforce_depth_limit();
+            book.update_id = update.last_update_id;
+        }
+        Ok(ApplyOutcome { update, continuity })
+    }
+}
+
+/// Replaces or removes the levels of one side of the book as they are visited.
+struct BookSide<'a> {
+    book: &'a mut LimitOrderBook,
+    bids: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for BookSide<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for BookSide<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of price levels")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let book = self.book;
+        while let Some(level) = seq.next_element::<PriceAndQuantity<f64, f64>>()? {
+            let limit = book.depth_limit.as_ref();
+            if self.bids {
+                if limit.is_none_or(|limit| limit.tracks_bid(level.0)) {
+                    Update::<ReplaceOrRemove>::process(&mut book.bids, level);
+                }
+            } else if limit.is_none_or(|limit| limit.tracks_ask(level.0)) {
+                Update::<ReplaceOrRemove>::process(&mut book.asks, level);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::ApplyDepthUpdate;
     #[cfg(feature = "event")]
     use crate::limit_order_book::event::Event;
+    use crate::limit_order_book::sequence::{Continuity, Market};
     use crate::DepthUpdate;
     use crate::LimitOrderBook;
     use crate::PriceAndQuantity;
+    use serde::de::DeserializeSeed;
 
     #[test]
     fn deserialize_from_snapshot() {
@@ -47,6 +286,7 @@ mod test {
                 PriceAndQuantity(27826.90000000, 4.80586000),
             ]
             .into(),
+            depth_limit: None,
         };
         assert_eq!(book, expected);
     }
@@ -105,7 +345,167 @@ mod test {
                 PriceAndQuantity(27826.90000000, 4.80586000),
             ]
             .into(),
+            ..Default::default()
         };
         assert_eq!(book, expected);
     }
+
+    const UPDATE: &str = r#"
+        {
+            "e": "depthUpdate",
+            "E": 123456789,
+            "s": "BNBBTC",
+            "U": 157,
+            "u": 160,
+            "b": [["27826.89000000", "2.50099000"], ["27826.10000000", "0.00000000"]],
+            "a": [["27826.90000000", "4.80586000"]]
+        }
+    "#;
+
+    fn book() -> LimitOrderBook {
+        let mut book = LimitOrderBook::new();
+        book.update_id = 156;
+        book.add_bid(PriceAndQuantity(27826.1, 0.69556));
+        book.add_ask(PriceAndQuantity(27826.91, 0.26959));
+        book
+    }
+
+    #[test]
+    fn apply_depth_update_in_place() {
+        let mut expected = book();
+        let update: DepthUpdate = serde_json::from_str(UPDATE).unwrap();
+        expected.apply(&update);
+
+        let mut book = book();
+        let mut deserializer = serde_json::Deserializer::from_str(UPDATE);
+        let outcome = ApplyDepthUpdate::new(&mut book, Market::Spot)
+            .deserialize(&mut deserializer)
+            .unwrap();
+
+        assert_eq!(book, expected);
+        assert_eq!(book.update_id, 160);
+        assert!(outcome.is_applied());
+        assert_eq!(outcome.update.first_update_id, 157);
+        assert!(outcome.update.bids.is_empty());
+        #[cfg(feature = "event-symbol")]
+        assert_eq!(outcome.update.event.symbol, "BNBBTC");
+        #[cfg(feature = "event-time")]
+        assert_eq!(outcome.update.event.time, 123456789);
+    }
+
+    #[test]
+    fn skipped_update_is_not_applied() {
+        let mut book = book();
+        book.update_id = 100;
+        let expected = book.clone();
+
+        let mut deserializer = serde_json::Deserializer::from_str(UPDATE);
+        let outcome = ApplyDepthUpdate::new(&mut book, Market::Spot)
+            .deserialize(&mut deserializer)
+            .unwrap();
+        assert_eq!(book, expected);
+        assert!(!outcome.is_applied());
+        assert_eq!(outcome.continuity, Continuity::Gap);
+        assert_eq!(outcome.update.last_update_id, 160);
+    }
+
+    #[test]
+    fn levels_before_ids_are_rejected() {
+        let mut book = book();
+        let mut deserializer =
+            serde_json::Deserializer::from_str(r#"{"b": [], "U": 157, "u": 160, "a": []}"#);
+        assert!(ApplyDepthUpdate::new(&mut book, Market::Spot)
+            .deserialize(&mut deserializer)
+            .is_err());
+    }
+
+    #[test]
+    fn serialize_snapshot_round_trip() {
+        let book = book();
+        let json = serde_json::to_string(&book).unwrap();
+        assert_eq!(
+            json,
+            r#"{"lastUpdateId":156,"bids":[["27826.1","0.69556"]],"asks":[["27826.91","0.26959"]]}"#
+        );
+        assert_eq!(serde_json::from_str::<LimitOrderBook>(&json).unwrap(), book);
+    }
+
+    #[test]
+    fn serialize_depth_update_round_trip() {
+        let update: DepthUpdate = serde_json::from_str(UPDATE).unwrap();
+        let json = serde_json::to_value(&update).unwrap();
+
+        // Best level first, as the exchange sends them.
+        assert_eq!(json["b"][0], serde_json::json!(["27826.89", "2.50099"]));
+        assert_eq!(json["U"], 157);
+        assert_eq!(json["u"], 160);
+        #[cfg(feature = "event-id")]
+        assert_eq!(json["e"], "depthUpdate");
+        #[cfg(feature = "event-time")]
+        assert_eq!(json["E"], 123456789);
+        #[cfg(feature = "event-symbol")]
+        assert_eq!(json["s"], "BNBBTC");
+
+        assert_eq!(serde_json::from_value::<DepthUpdate>(json).unwrap(), update);
+    }
+
+    const FUTURES_UPDATE: &str = r#"
+        {
+            "e": "depthUpdate",
+            "E": 123456789,
+            "T": 123456788,
+            "s": "BTCUSDT",
+            "U": 157,
+            "u": 160,
+            "pu": 149,
+            "b": [["0.0024", "10"]],
+            "a": [["0.0026", "100"]]
+        }
+    "#;
+
+    #[test]
+    fn deserialize_futures_depth_update() {
+        let update: DepthUpdate = serde_json::from_str(FUTURES_UPDATE).unwrap();
+        assert_eq!(update.previous_update_id, Some(149));
+        assert_eq!(update.transaction_time, Some(123456788));
+        #[cfg(feature = "event-time")]
+        assert_eq!(update.event.time, 123456789);
+
+        let json = serde_json::to_value(&update).unwrap();
+        assert_eq!(json["pu"], 149);
+        assert_eq!(json["T"], 123456788);
+        assert!(serde_json::to_value(DepthUpdate::default())
+            .unwrap()
+            .get("pu")
+            .is_none());
+    }
+
+    #[test]
+    fn apply_futures_depth_update_in_place() {
+        let apply = |update_id, market| {
+            let mut book = LimitOrderBook::new();
+            book.update_id = update_id;
+            let mut deserializer = serde_json::Deserializer::from_str(FUTURES_UPDATE);
+            let outcome = ApplyDepthUpdate::new(&mut book, market)
+                .deserialize(&mut deserializer)
+                .unwrap();
+            (book, outcome.continuity)
+        };
+
+        let (book, continuity) = apply(149, Market::UsdMFutures);
+        assert_eq!(continuity, Continuity::Continuous);
+        assert_eq!(book.update_id, 160);
+        assert_eq!(**book.bids(), [PriceAndQuantity(0.0024, 10.)]);
+
+        // `pu` doesn't chain on the book and the range doesn't contain it.
+        let (book, continuity) = apply(150, Market::UsdMFutures);
+        assert_eq!(continuity, Continuity::Gap);
+        assert_eq!(book.update_id, 150);
+        assert!(book.bids().is_empty());
+
+        // The caller's rule is used whatever the update carries, `pu` means nothing to the spot one.
+        let (book, continuity) = apply(149, Market::Spot);
+        assert_eq!(continuity, Continuity::Gap);
+        assert_eq!(book.update_id, 149);
+    }
 }
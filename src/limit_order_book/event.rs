@@ -1,16 +1,16 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
-#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Default)]
 pub struct Event {
     #[cfg(feature = "event-id")]
-    #[serde(alias = "e")]
+    #[serde(alias = "e", rename(serialize = "e"))]
     pub id: String,
     #[cfg(feature = "event-time")]
-    #[serde(alias = "E")]
+    #[serde(alias = "E", rename(serialize = "E"))]
     pub time: u64,
     #[cfg(feature = "event-symbol")]
-    #[serde(alias = "s")]
+    #[serde(alias = "s", rename(serialize = "s"))]
     pub symbol: String,
 }
 
@@ -0,0 +1,317 @@
+use super::sequence::{Continuity, Market};
+use super::{DepthUpdate, LimitOrderBook};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+/// Updates buffered per book while it awaits a snapshot, past that the oldest are dropped.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 10_000;
+
+/// Whether a book can take depth updates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncState {
+    /// No snapshot yet, or a gap was detected. Updates are buffered until [BookManager::snapshot].
+    AwaitingSnapshot,
+    Synced,
+}
+
+/// What became of a routed update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routed {
+    Applied,
+    /// Kept to be replayed on top of the next snapshot.
+    Buffered,
+    /// Already reflected in the book, dropped.
+    Stale,
+    /// Updates were missed, the book now awaits a new snapshot and the update is buffered for it.
+    Gap,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownSymbol(pub String);
+
+impl Display for UnknownSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no book for symbol {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSymbol {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Instrument {
+    book: LimitOrderBook,
+    market: Market,
+    state: SyncState,
+    buffer: VecDeque<DepthUpdate>,
+    buffer_capacity: usize,
+}
+
+impl Instrument {
+    fn route(&mut self, update: DepthUpdate) -> Routed {
+        if self.state == SyncState::AwaitingSnapshot {
+            self.buffer(update);
+            return Routed::Buffered;
+        }
+        match self.market.continuity(&update, self.book.update_id) {
+            Continuity::Continuous => {
+                self.book.apply(&update);
+                Routed::Applied
+            }
+            Continuity::Stale => Routed::Stale,
+            // The update may well follow the next snapshot, keep it.
+            Continuity::Gap => {
+                self.state = SyncState::AwaitingSnapshot;
+                self.buffer(update);
+                Routed::Gap
+            }
+        }
+    }
+
+    /// The newest updates are the ones that connect to the next snapshot, the oldest go first.
+    fn buffer(&mut self, update: DepthUpdate) {
+        if self.buffer.len() >= self.buffer_capacity {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(update);
+    }
+}
+
+/// One [LimitOrderBook] per symbol, each synchronised on its own.
+/// A book starts awaiting its snapshot, updates received meanwhile are buffered and replayed once it arrives.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookManager {
+    instruments: HashMap<String, Instrument>,
+    buffer_capacity: usize,
+}
+
+impl Default for BookManager {
+    fn default() -> Self {
+        Self {
+            instruments: HashMap::new(),
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+        }
+    }
+}
+
+impl BookManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates buffered per book while it awaits a snapshot, at least one. Applies to the symbols added afterwards.
+    pub fn with_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity.max(1);
+        self
+    }
+
+    /// Starts tracking `symbol`, false if it already was.
+    pub fn add(&mut self, symbol: impl Into<String>, market: Market) -> bool {
+        let mut added = false;
+        self.instruments.entry(symbol.into()).or_insert_with(|| {
+            added = true;
+            Instrument {
+                book: LimitOrderBook::new(),
+                market,
+                state: SyncState::AwaitingSnapshot,
+                buffer: VecDeque::new(),
+                buffer_capacity: self.buffer_capacity,
+            }
+        });
+        added
+    }
+
+    /// Stops tracking `symbol`, returning its book.
+    pub fn remove(&mut self, symbol: &str) -> Option<LimitOrderBook> {
+        self.instruments
+            .remove(symbol)
+            .map(|instrument| instrument.book)
+    }
+
+    pub fn book(&self, symbol: &str) -> Option<&LimitOrderBook> {
+        self.instruments
+            .get(symbol)
+            .map(|instrument| &instrument.book)
+    }
+
+    pub fn state(&self, symbol: &str) -> Option<SyncState> {
+        self.instruments
+            .get(symbol)
+            .map(|instrument| instrument.state)
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.instruments.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    /// Installs a snapshot, keeping the book's depth limit, and replays the buffered updates on top of it.
+    /// Returns [SyncState::AwaitingSnapshot] if the buffer doesn't connect to the snapshot.
+    pub fn snapshot(
+        &mut self,
+        symbol: &str,
+        book: LimitOrderBook,
+    ) -> Result<SyncState, UnknownSymbol> {
+        let instrument = self.instrument(symbol)?;
+        instrument.book.replace(book);
+        instrument.state = SyncState::Synced;
+        // After a gap the rest of the buffer is buffered again for the next snapshot.
+        for update in std::mem::take(&mut instrument.buffer) {
+            instrument.route(update);
+        }
+        Ok(instrument.state)
+    }
+
+    /// Routes an update to the book of `symbol`.
+    pub fn apply_to(&mut self, symbol: &str, update: DepthUpdate) -> Result<Routed, UnknownSymbol> {
+        Ok(self.instrument(symbol)?.route(update))
+    }
+
+    /// Routes an update to the book of its `event.symbol`.
+    #[cfg(feature = "event-symbol")]
+    pub fn apply(&mut self, update: DepthUpdate) -> Result<Routed, UnknownSymbol> {
+        let symbol = update.event.symbol.clone();
+        self.apply_to(&symbol, update)
+    }
+
+    fn instrument(&mut self, symbol: &str) -> Result<&mut Instrument, UnknownSymbol> {
+        self.instruments
+            .get_mut(symbol)
+            .ok_or_else(|| UnknownSymbol(symbol.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PriceAndQuantity;
+
+    fn update(first: u64, last: u64, bid: f64) -> DepthUpdate {
+        DepthUpdate {
+            first_update_id: first,
+            last_update_id: last,
+            bids: vec![PriceAndQuantity(bid, 1.)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(update_id: u64) -> LimitOrderBook {
+        LimitOrderBook::from_sides(update_id, Default::default(), Default::default())
+    }
+
+    #[test]
+    fn buffers_until_snapshot() {
+        let mut manager = BookManager::new();
+        assert!(manager.add("BTCUSDT", Market::Spot));
+        assert!(!manager.add("BTCUSDT", Market::Spot));
+
+        assert_eq!(
+            manager.apply_to("BTCUSDT", update(1, 4, 1.)),
+            Ok(Routed::Buffered)
+        );
+        assert_eq!(
+            manager.apply_to("BTCUSDT", update(6, 8, 2.)),
+            Ok(Routed::Buffered)
+        );
+        assert_eq!(
+            manager.snapshot("BTCUSDT", snapshot(6)),
+            Ok(SyncState::Synced)
+        );
+        let book = manager.book("BTCUSDT").unwrap();
+        assert_eq!(book.update_id, 8);
+        assert_eq!(**book.bids(), [PriceAndQuantity(2., 1.)]);
+
+        assert_eq!(
+            manager.apply_to("BTCUSDT", update(9, 9, 3.)),
+            Ok(Routed::Applied)
+        );
+    }
+
+    #[test]
+    fn resnapshot_keeps_depth_limit() {
+        let mut manager = BookManager::new();
+        manager.add("BTCUSDT", Market::Spot);
+        let mut limited = snapshot(1);
+        limited.set_depth_limit(5);
+        manager.snapshot("BTCUSDT", limited).unwrap();
+        manager.snapshot("BTCUSDT", snapshot(2)).unwrap();
+        let book = manager.book("BTCUSDT").unwrap();
+        assert_eq!(book.update_id, 2);
+        assert_eq!(book.depth_limit().unwrap().levels, 5);
+    }
+
+    #[test]
+    fn gap_awaits_new_snapshot() {
+        let mut manager = BookManager::new();
+        manager.add("ETHUSDT", Market::Spot);
+        manager.snapshot("ETHUSDT", snapshot(10)).unwrap();
+        assert_eq!(
+            manager.apply_to("ETHUSDT", update(20, 21, 1.)),
+            Ok(Routed::Gap)
+        );
+        assert_eq!(manager.state("ETHUSDT"), Some(SyncState::AwaitingSnapshot));
+        assert_eq!(
+            manager.apply_to("ETHUSDT", update(22, 22, 1.)),
+            Ok(Routed::Buffered)
+        );
+
+        // The update that revealed the gap is replayed on the next snapshot.
+        assert_eq!(
+            manager.snapshot("ETHUSDT", snapshot(20)),
+            Ok(SyncState::Synced)
+        );
+        assert_eq!(manager.book("ETHUSDT").unwrap().update_id, 22);
+    }
+
+    #[test]
+    fn buffer_keeps_the_newest_updates() {
+        let mut manager = BookManager::new().with_buffer_capacity(2);
+        manager.add("BTCUSDT", Market::Spot);
+        for id in 1..=5 {
+            manager
+                .apply_to("BTCUSDT", update(id, id, id as f64))
+                .unwrap();
+        }
+        assert_eq!(
+            manager.snapshot("BTCUSDT", snapshot(3)),
+            Ok(SyncState::Synced)
+        );
+        let book = manager.book("BTCUSDT").unwrap();
+        assert_eq!(book.update_id, 5);
+        assert_eq!(
+            **book.bids(),
+            [PriceAndQuantity(4., 1.), PriceAndQuantity(5., 1.)]
+        );
+    }
+
+    #[test]
+    fn unknown_and_removed_symbols() {
+        let mut manager = BookManager::new();
+        manager.add("BTCUSDT", Market::Spot);
+        manager.add("BTCUSDT_PERP", Market::UsdMFutures);
+        assert_eq!(manager.len(), 2);
+        assert!(manager.remove("BTCUSDT").is_some());
+        assert_eq!(manager.symbols().collect::<Vec<_>>(), ["BTCUSDT_PERP"]);
+        assert_eq!(
+            manager.apply_to("BTCUSDT", update(1, 1, 1.)),
+            Err(UnknownSymbol("BTCUSDT".to_owned()))
+        );
+    }
+
+    #[cfg(feature = "event-symbol")]
+    #[test]
+    fn routes_by_event_symbol() {
+        let mut manager = BookManager::new();
+        manager.add("BNBBTC", Market::Spot);
+        manager.snapshot("BNBBTC", snapshot(1)).unwrap();
+        let mut update = update(2, 2, 1.);
+        update.event.symbol = "BNBBTC".to_owned();
+        assert_eq!(manager.apply(update), Ok(Routed::Applied));
+    }
+}
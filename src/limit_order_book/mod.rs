@@ -1,21 +1,186 @@
 use super::{Asks, Bids};
-use crate::ops::{update_strategies::ReplaceOrRemove, Update};
+use crate::ops::{update_strategies::ReplaceOrRemove, Merge, Update};
 use crate::PriceAndQuantity;
+pub use aggregate::TopOfBook;
+pub use depth_limit::DepthLimit;
+#[cfg(feature = "serde")]
+pub use deserialize::{ApplyDepthUpdate, ApplyOutcome};
 #[cfg(feature = "event")]
 use event::Event;
+pub use manager::{BookManager, Routed, SyncState, UnknownSymbol, DEFAULT_BUFFER_CAPACITY};
+pub use normalized::{LevelChange, MarketUpdate, Side, Venue};
+pub use order_flow::{Depletion, Execution, OrderFlowBook};
+pub use partial::{BookTicker, PartialDepth};
+use sequence::{Continuity, Market, SequenceRule};
 #[cfg(feature = "serde")]
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+#[cfg(feature = "codec")]
+pub use versioned::Versioned;
 
+mod aggregate;
+mod depth_limit;
+#[cfg(feature = "serde")]
 mod deserialize;
 #[cfg(feature = "event")]
 pub mod event;
+mod manager;
+mod normalized;
+mod order_flow;
+mod partial;
+#[cfg(feature = "grpc")]
+mod scaled;
+pub mod sequence;
+#[cfg(feature = "codec")]
+mod versioned;
 
 #[cfg(feature = "grpc")]
 pub mod protos {
     include!(concat!(env!("OUT_DIR"), "/protos.rs"));
 
+    /// Encoded `FileDescriptorSet` of the protos, served through gRPC reflection.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/limitorderbook_descriptor.bin"));
+
+    use super::scaled::decoded;
+    use super::DepthUpdate as NativeDepthUpdate;
+    #[cfg(feature = "event")]
+    use super::Event as NativeEvent;
     use super::LimitOrderBook as NativeLOB;
+    use super::TopOfBook as NativeTopOfBook;
+
+    fn levels<T>(side: &T) -> Vec<PriceAndQuantity>
+    where
+        T: std::ops::Deref<Target = std::vec::Vec<super::PriceAndQuantity<f64, f64>>>,
+    {
+        side.iter()
+            .map(|p_n_q| PriceAndQuantity {
+                price: p_n_q.0,
+                quantity: p_n_q.1,
+            })
+            .collect()
+    }
+
+    fn native_levels<T>(levels: Vec<PriceAndQuantity>) -> T
+    where
+        T: From<std::vec::Vec<super::PriceAndQuantity<f64, f64>>>,
+    {
+        levels
+            .into_iter()
+            .map(|PriceAndQuantity { price, quantity }| super::PriceAndQuantity(price, quantity))
+            .collect::<std::vec::Vec<_>>()
+            .into()
+    }
+
+    /// Fields left out by the `event-*` features are sent as defaults.
+    #[cfg(feature = "event")]
+    impl From<&NativeEvent> for Event {
+        #[allow(unused_variables)]
+        fn from(event: &NativeEvent) -> Self {
+            Event {
+                #[cfg(feature = "event-id")]
+                id: event.id.clone(),
+                #[cfg(not(feature = "event-id"))]
+                id: String::new(),
+                #[cfg(feature = "event-time")]
+                time: event.time,
+                #[cfg(not(feature = "event-time"))]
+                time: 0,
+                #[cfg(feature = "event-symbol")]
+                symbol: event.symbol.clone(),
+                #[cfg(not(feature = "event-symbol"))]
+                symbol: String::new(),
+            }
+        }
+    }
+
+    /// Fields left out by the `event-*` features are dropped.
+    #[cfg(feature = "event")]
+    impl From<Event> for NativeEvent {
+        #[allow(unused_variables)]
+        fn from(event: Event) -> Self {
+            NativeEvent {
+                #[cfg(feature = "event-id")]
+                id: event.id,
+                #[cfg(feature = "event-time")]
+                time: event.time,
+                #[cfg(feature = "event-symbol")]
+                symbol: event.symbol,
+            }
+        }
+    }
+
+    impl From<&NativeDepthUpdate> for DepthUpdate {
+        fn from(update: &NativeDepthUpdate) -> Self {
+            DepthUpdate {
+                first_update_id: update.first_update_id,
+                last_update_id: update.last_update_id,
+                bids: Some(Bids {
+                    bids: levels(&update.bids),
+                    scaled: Vec::new(),
+                }),
+                asks: Some(Asks {
+                    asks: levels(&update.asks),
+                    scaled: Vec::new(),
+                }),
+                #[cfg(feature = "event")]
+                event: Some((&update.event).into()),
+                #[cfg(not(feature = "event"))]
+                event: None,
+                previous_update_id: update.previous_update_id,
+                transaction_time: update.transaction_time,
+                scale: None,
+            }
+        }
+    }
+
+    impl From<NativeDepthUpdate> for DepthUpdate {
+        fn from(update: NativeDepthUpdate) -> Self {
+            (&update).into()
+        }
+    }
+
+    /// With the `event` feature a missing event is the default one, peers built without it don't send any.
+    /// Without the feature the event is dropped.
+    impl From<DepthUpdate> for NativeDepthUpdate {
+        fn from(update: DepthUpdate) -> Self {
+            Self {
+                #[cfg(feature = "event")]
+                event: update.event.map(Into::into).unwrap_or_default(),
+                first_update_id: update.first_update_id,
+                last_update_id: update.last_update_id,
+                previous_update_id: update.previous_update_id,
+                transaction_time: update.transaction_time,
+                bids: native_levels(
+                    update
+                        .bids
+                        .map(|bids| decoded(bids.bids, bids.scaled, update.scale.as_ref()))
+                        .unwrap_or_default(),
+                ),
+                asks: native_levels(
+                    update
+                        .asks
+                        .map(|asks| decoded(asks.asks, asks.scaled, update.scale.as_ref()))
+                        .unwrap_or_default(),
+                ),
+            }
+        }
+    }
+
+    impl From<NativeTopOfBook> for TopOfBook {
+        fn from(top: NativeTopOfBook) -> Self {
+            let level = |p_n_q: super::PriceAndQuantity<f64, f64>| PriceAndQuantity {
+                price: p_n_q.0,
+                quantity: p_n_q.1,
+            };
+            TopOfBook {
+                best_bid: top.best_bid.map(level),
+                best_ask: top.best_ask.map(level),
+                spread: top.spread(),
+                mid: top.mid(),
+            }
+        }
+    }
 
     impl From<NativeLOB> for LimitOrderBook {
         fn from(og: NativeLOB) -> Self {
@@ -30,6 +195,7 @@ pub mod protos {
                             quantity: p_n_q.1,
                         })
                         .collect(),
+                    scaled: Vec::new(),
                 }),
                 asks: Some(Asks {
                     asks: og
@@ -40,58 +206,55 @@ pub mod protos {
                             quantity: p_n_q.1,
                         })
                         .collect(),
+                    scaled: Vec::new(),
                 }),
+                scale: None,
+                top_of_book: None,
             }
         }
     }
 
+    /// The top of book is dropped, the native book has its own.
     impl From<LimitOrderBook> for NativeLOB {
         fn from(book: LimitOrderBook) -> Self {
             let LimitOrderBook {
                 update_id,
                 bids,
                 asks,
+                scale,
+                ..
             } = book;
 
-            let bids: super::Bids = bids
-                .map_or_else(std::vec::Vec::new, |bids| {
-                    bids.bids
-                        .into_iter()
-                        .map(|PriceAndQuantity { price, quantity }| {
-                            super::PriceAndQuantity(price, quantity)
-                        })
-                        .collect()
-                })
-                .into();
-
-            let asks: super::Asks = asks
-                .map_or_else(std::vec::Vec::new, |asks| {
-                    asks.asks
-                        .into_iter()
-                        .map(|PriceAndQuantity { price, quantity }| {
-                            super::PriceAndQuantity(price, quantity)
-                        })
-                        .collect()
-                })
-                .into();
+            let bids = native_levels(
+                bids.map(|bids| decoded(bids.bids, bids.scaled, scale.as_ref()))
+                    .unwrap_or_default(),
+            );
+            let asks = native_levels(
+                asks.map(|asks| decoded(asks.asks, asks.scaled, scale.as_ref()))
+                    .unwrap_or_default(),
+            );
 
             Self {
                 update_id,
                 bids,
                 asks,
+                depth_limit: None,
             }
         }
     }
 }
 
 #[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
-#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct LimitOrderBook {
-    #[serde(alias = "lastUpdateId")]
+    #[serde(alias = "lastUpdateId", rename(serialize = "lastUpdateId"))]
     pub update_id: u64,
     bids: Bids,
     asks: Asks,
+    #[serde(skip)]
+    #[cfg_attr(feature = "codec", codec(skip))]
+    depth_limit: Option<DepthLimit>,
 }
 
 impl LimitOrderBook {
@@ -100,15 +263,69 @@ impl LimitOrderBook {
             update_id: 0,
             bids: Bids::new(),
             asks: Asks::new(),
+            depth_limit: None,
         }
     }
 
+    /// Careful, the sides are taken as they are, they must already be sorted in their side's order.
+    pub fn from_sides(update_id: u64, bids: Bids, asks: Asks) -> LimitOrderBook {
+        LimitOrderBook {
+            update_id,
+            bids,
+            asks,
+            depth_limit: None,
+        }
+    }
+
+    pub fn bids(&self) -> &Bids {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &Asks {
+        &self.asks
+    }
+
     pub fn add_bid(&mut self, bid: PriceAndQuantity<f64, f64>) {
-        Update::<ReplaceOrRemove>::process(&mut self.bids, bid)
+        if self
+            .depth_limit
+            .as_ref()
+            .is_none_or(|limit| limit.tracks_bid(bid.0))
+        {
+            Update::<ReplaceOrRemove>::process(&mut self.bids, bid);
+            self.enforce_depth_limit();
+        }
     }
 
     pub fn add_ask(&mut self, ask: PriceAndQuantity<f64, f64>) {
-        Update::<ReplaceOrRemove>::process(&mut self.asks, ask)
+        if self
+            .depth_limit
+            .as_ref()
+            .is_none_or(|limit| limit.tracks_ask(ask.0))
+        {
+            Update::<ReplaceOrRemove>::process(&mut self.asks, ask);
+            self.enforce_depth_limit();
+        }
+    }
+
+    /// Applies both sides of `update` with the [ReplaceOrRemove] strategy, merging each side in a single pass.
+    /// It does not check continuity, see [DepthUpdate::skip_update].
+    pub fn apply(&mut self, update: &DepthUpdate) {
+        let limit = self.depth_limit.clone().unwrap_or_default();
+        let bids = update.bids.iter().filter(|bid| limit.tracks_bid(bid.0));
+        let asks = update.asks.iter().filter(|ask| limit.tracks_ask(ask.0));
+        Merge::<ReplaceOrRemove>::merge(&mut self.bids, bids.copied());
+        Merge::<ReplaceOrRemove>::merge(&mut self.asks, asks.copied());
+        self.enforce_depth_limit();
+        self.update_id = update.last_update_id;
+    }
+
+    /// Keeps the best `levels` of each side. Unlike [LimitOrderBook::set_depth_limit] the book is not marked incomplete,
+    /// use it for venues that republish the levels coming back into range.
+    pub fn truncate(&mut self, levels: usize) {
+        let bids = self.bids.len().saturating_sub(levels);
+        self.bids.drain(..bids);
+        let asks = self.asks.len().saturating_sub(levels);
+        self.asks.drain(..asks);
     }
 
     // Careful, This is a cheap extend and wont respect Ordering.
@@ -119,6 +336,7 @@ impl LimitOrderBook {
             update_id,
             bids,
             asks,
+            ..
         } = other;
         self.bids.extend(bids.iter().copied());
         self.asks.extend(asks.iter().copied());
@@ -136,19 +354,35 @@ impl Display for LimitOrderBook {
     }
 }
 
-#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[derive(PartialEq, Debug, Clone, Default)]
 pub struct DepthUpdate {
     #[cfg(feature = "event")]
     #[serde(flatten)]
     pub event: Event,
-    #[serde(alias = "U")]
+    #[serde(alias = "U", rename(serialize = "U"))]
     pub first_update_id: u64,
-    #[serde(alias = "u")]
+    #[serde(alias = "u", rename(serialize = "u"))]
     pub last_update_id: u64,
-    #[serde(alias = "b")]
+    /// Futures only, the last update id of the previous event.
+    #[serde(
+        alias = "pu",
+        rename(serialize = "pu"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub previous_update_id: Option<u64>,
+    /// Futures only, transaction time in milliseconds.
+    #[serde(
+        alias = "T",
+        rename(serialize = "T"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub transaction_time: Option<u64>,
+    #[serde(alias = "b", rename(serialize = "b"))]
     pub bids: Bids,
-    #[serde(alias = "a")]
+    #[serde(alias = "a", rename(serialize = "a"))]
     pub asks: Asks,
 }
 
@@ -167,17 +401,89 @@ impl Display for DepthUpdate {
 }
 
 impl DepthUpdate {
-    /// A valid update [a, b] should overlap or at least
-    /// not gap between the last update id and the new update range.
-    pub fn skip_update(&self, last_book_id: u64) -> bool {
-        let (a, b) = (self.first_update_id, self.last_update_id);
-        last_book_id + 1 < a || b + 1 < last_book_id
+    /// Whether the update can't be applied on top of the book under the rule of `market`,
+    /// e.g. for [Market::Spot] it should overlap or at least not gap between the last update id and its range.
+    pub fn skip_update(&self, market: Market, last_book_id: u64) -> bool {
+        market.continuity(self, last_book_id) != Continuity::Continuous
+    }
+
+    /// Checks the update against the last update id of the book with a venue's [SequenceRule].
+    pub fn continuity<R: SequenceRule>(&self, last_book_id: u64) -> Continuity {
+        R::continuity(self, last_book_id)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::DepthUpdate;
+    use super::{DepthUpdate, LimitOrderBook, Market};
+    use crate::PriceAndQuantity;
+
+    #[test]
+    fn apply_matches_add() {
+        let mut expected = LimitOrderBook::new();
+        expected.add_bid(PriceAndQuantity(1., 1.));
+        expected.add_bid(PriceAndQuantity(2., 1.));
+        expected.add_ask(PriceAndQuantity(3., 1.));
+        let mut book = expected.clone();
+
+        let update = DepthUpdate {
+            #[cfg(feature = "event")]
+            event: Default::default(),
+            first_update_id: 1,
+            last_update_id: 2,
+            bids: vec![PriceAndQuantity(1., 0.), PriceAndQuantity(1.5, 2.)].into(),
+            asks: vec![PriceAndQuantity(4., 1.), PriceAndQuantity(3., 3.)].into(),
+            ..Default::default()
+        };
+        for bid in update.bids.iter() {
+            expected.add_bid(*bid);
+        }
+        for ask in update.asks.iter() {
+            expected.add_ask(*ask);
+        }
+        expected.update_id = 2;
+
+        book.apply(&update);
+        assert_eq!(book, expected);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn depth_update_protobuf_round_trip() {
+        use super::protos;
+        use prost::Message;
+
+        let mut update = DepthUpdate {
+            first_update_id: 5,
+            last_update_id: 6,
+            previous_update_id: Some(4),
+            bids: vec![PriceAndQuantity(1., 0.), PriceAndQuantity(1.5, 2.)].into(),
+            asks: vec![PriceAndQuantity(3., 1.)].into(),
+            ..Default::default()
+        };
+        #[cfg(feature = "event-symbol")]
+        {
+            update.event.symbol = "BNBBTC".to_owned();
+        }
+        let encoded = protos::DepthUpdate::from(&update).encode_to_vec();
+        let decoded = protos::DepthUpdate::decode(&encoded[..]).unwrap();
+        assert_eq!(decoded.transaction_time, None);
+        assert_eq!(DepthUpdate::from(decoded.clone()), update);
+
+        update.transaction_time = Some(7);
+        assert_eq!(protos::DepthUpdate::from(&update).transaction_time, Some(7));
+
+        let without_event = protos::DepthUpdate {
+            event: None,
+            ..decoded
+        };
+        update.transaction_time = None;
+        #[cfg(feature = "event")]
+        {
+            update.event = Default::default();
+        }
+        assert_eq!(DepthUpdate::from(without_event), update);
+    }
 
     #[test]
     fn skip_update_works() {
@@ -187,16 +493,26 @@ mod test {
             ..Default::default()
         };
         // coming from the left there is a gap; skip.
-        assert!(update.skip_update(0));
+        assert!(update.skip_update(Market::Spot, 0));
         // continuous coming from the left, pass
-        assert!(!update.skip_update(1));
+        assert!(!update.skip_update(Market::Spot, 1));
         // overlap coming from the left, pass
-        assert!(!update.skip_update(2));
+        assert!(!update.skip_update(Market::Spot, 2));
         // overlap coming from the right, pass
-        assert!(!update.skip_update(3));
+        assert!(!update.skip_update(Market::Spot, 3));
         //continuity coming for the right at the second bound; pass
-        assert!(!update.skip_update(4));
+        assert!(!update.skip_update(Market::Spot, 4));
         // gap coming from the right; skip
-        assert!(update.skip_update(5));
+        assert!(update.skip_update(Market::Spot, 5));
+
+        let update = DepthUpdate {
+            previous_update_id: Some(1),
+            ..update
+        };
+        // chained on the previous update; pass
+        assert!(!update.skip_update(Market::UsdMFutures, 1));
+        // the spot rule ignores `pu`; skip
+        assert!(update.skip_update(Market::Spot, 0));
+        assert!(update.skip_update(Market::UsdMFutures, 0));
     }
 }
@@ -0,0 +1,381 @@
+#[cfg(feature = "event")]
+use super::event::Event;
+use super::{DepthUpdate, LimitOrderBook};
+use crate::ops::update_side;
+use crate::{Asks, Bids, PriceAndQuantity};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Venue {
+    Binance,
+    BinanceUsdMFutures,
+    Coinbase,
+    Kraken,
+    Okx,
+    Bybit,
+}
+
+impl Display for Venue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Venue::Binance => "binance",
+            Venue::BinanceUsdMFutures => "binance_usd_m_futures",
+            Venue::Coinbase => "coinbase",
+            Venue::Kraken => "kraken",
+            Venue::Okx => "okx",
+            Venue::Bybit => "bybit",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// New quantity of one level, zero removes it.
+#[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct LevelChange {
+    pub side: Side,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// Book update in the same shape whatever the venue, every adapter can emit one.
+/// The ids carry the venue's own sequence, see [DepthUpdate] and [super::sequence] for how they chain.
+#[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct MarketUpdate {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Milliseconds since the epoch, as stamped by the venue.
+    pub exchange_time: u64,
+    /// Milliseconds since the epoch, as stamped on arrival.
+    pub receive_time: u64,
+    pub first_update_id: u64,
+    pub last_update_id: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub previous_update_id: Option<u64>,
+    pub changes: Vec<LevelChange>,
+    /// The changes are the whole book rather than a diff.
+    pub snapshot: bool,
+}
+
+impl MarketUpdate {
+    /// Bids come first, each side best level first.
+    pub fn from_depth_update(
+        venue: Venue,
+        symbol: impl Into<String>,
+        exchange_time: u64,
+        receive_time: u64,
+        update: &DepthUpdate,
+        snapshot: bool,
+    ) -> Self {
+        let change = |side| {
+            move |level: &PriceAndQuantity<f64, f64>| LevelChange {
+                side,
+                price: level.0,
+                quantity: level.1,
+            }
+        };
+        let changes = update
+            .bids
+            .iter()
+            .rev()
+            .map(change(Side::Bid))
+            .chain(update.asks.iter().rev().map(change(Side::Ask)))
+            .collect();
+
+        Self {
+            venue,
+            symbol: symbol.into(),
+            exchange_time,
+            receive_time,
+            first_update_id: update.first_update_id,
+            last_update_id: update.last_update_id,
+            previous_update_id: update.previous_update_id,
+            changes,
+            snapshot,
+        }
+    }
+
+    /// The changes as a [DepthUpdate], on repeated prices the last change wins.
+    pub fn depth_update(&self) -> DepthUpdate {
+        let side = |side| {
+            self.changes
+                .iter()
+                .filter(|change| change.side == side)
+                .map(|change| PriceAndQuantity(change.price, change.quantity))
+                .collect()
+        };
+
+        DepthUpdate {
+            #[cfg(feature = "event")]
+            event: self.event(),
+            first_update_id: self.first_update_id,
+            last_update_id: self.last_update_id,
+            previous_update_id: self.previous_update_id,
+            bids: update_side(side(Side::Bid)),
+            asks: update_side(side(Side::Ask)),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "event")]
+    fn event(&self) -> Event {
+        Event {
+            #[cfg(feature = "event-id")]
+            id: "depthUpdate".to_owned(),
+            #[cfg(feature = "event-time")]
+            time: self.exchange_time,
+            #[cfg(feature = "event-symbol")]
+            symbol: self.symbol.clone(),
+        }
+    }
+}
+
+impl LimitOrderBook {
+    /// Applies the changes, a snapshot first clears the book. Sequencing is left to the caller.
+    pub fn apply_market_update(&mut self, update: &MarketUpdate) {
+        if update.snapshot {
+            self.bids = Bids::new();
+            self.asks = Asks::new();
+            if let Some(limit) = &self.depth_limit {
+                self.set_depth_limit(limit.levels);
+            }
+        }
+        self.apply(&update.depth_update());
+    }
+}
+
+#[cfg(feature = "grpc")]
+mod protos {
+    use super::super::protos;
+    use super::{LevelChange, MarketUpdate, Side, Venue};
+    use prost::UnknownEnumValue;
+
+    impl From<Venue> for protos::Venue {
+        fn from(venue: Venue) -> Self {
+            match venue {
+                Venue::Binance => protos::Venue::Binance,
+                Venue::BinanceUsdMFutures => protos::Venue::BinanceUsdMFutures,
+                Venue::Coinbase => protos::Venue::Coinbase,
+                Venue::Kraken => protos::Venue::Kraken,
+                Venue::Okx => protos::Venue::Okx,
+                Venue::Bybit => protos::Venue::Bybit,
+            }
+        }
+    }
+
+    impl TryFrom<i32> for Venue {
+        type Error = UnknownEnumValue;
+
+        fn try_from(value: i32) -> Result<Self, Self::Error> {
+            match protos::Venue::try_from(value)? {
+                protos::Venue::Unspecified => Err(UnknownEnumValue(value)),
+                protos::Venue::Binance => Ok(Venue::Binance),
+                protos::Venue::BinanceUsdMFutures => Ok(Venue::BinanceUsdMFutures),
+                protos::Venue::Coinbase => Ok(Venue::Coinbase),
+                protos::Venue::Kraken => Ok(Venue::Kraken),
+                protos::Venue::Okx => Ok(Venue::Okx),
+                protos::Venue::Bybit => Ok(Venue::Bybit),
+            }
+        }
+    }
+
+    impl From<Side> for protos::Side {
+        fn from(side: Side) -> Self {
+            match side {
+                Side::Bid => protos::Side::Bid,
+                Side::Ask => protos::Side::Ask,
+            }
+        }
+    }
+
+    impl TryFrom<i32> for Side {
+        type Error = UnknownEnumValue;
+
+        fn try_from(value: i32) -> Result<Self, Self::Error> {
+            match protos::Side::try_from(value)? {
+                protos::Side::Unspecified => Err(UnknownEnumValue(value)),
+                protos::Side::Bid => Ok(Side::Bid),
+                protos::Side::Ask => Ok(Side::Ask),
+            }
+        }
+    }
+
+    impl From<MarketUpdate> for protos::MarketUpdate {
+        fn from(update: MarketUpdate) -> Self {
+            protos::MarketUpdate {
+                venue: protos::Venue::from(update.venue).into(),
+                symbol: update.symbol,
+                exchange_time: update.exchange_time,
+                receive_time: update.receive_time,
+                first_update_id: update.first_update_id,
+                last_update_id: update.last_update_id,
+                previous_update_id: update.previous_update_id,
+                changes: update
+                    .changes
+                    .into_iter()
+                    .map(|change| protos::LevelChange {
+                        side: protos::Side::from(change.side).into(),
+                        price: change.price,
+                        quantity: change.quantity,
+                    })
+                    .collect(),
+                snapshot: update.snapshot,
+            }
+        }
+    }
+
+    /// Fails on an enum value this version doesn't know of.
+    impl TryFrom<protos::MarketUpdate> for MarketUpdate {
+        type Error = UnknownEnumValue;
+
+        fn try_from(update: protos::MarketUpdate) -> Result<Self, Self::Error> {
+            Ok(MarketUpdate {
+                venue: update.venue.try_into()?,
+                symbol: update.symbol,
+                exchange_time: update.exchange_time,
+                receive_time: update.receive_time,
+                first_update_id: update.first_update_id,
+                last_update_id: update.last_update_id,
+                previous_update_id: update.previous_update_id,
+                changes: update
+                    .changes
+                    .into_iter()
+                    .map(|change| {
+                        Ok(LevelChange {
+                            side: change.side.try_into()?,
+                            price: change.price,
+                            quantity: change.quantity,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                snapshot: update.snapshot,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn update() -> MarketUpdate {
+        MarketUpdate {
+            venue: Venue::Kraken,
+            symbol: "XBT/USD".to_owned(),
+            exchange_time: 1_700_000_000_000,
+            receive_time: 1_700_000_000_005,
+            first_update_id: 3,
+            last_update_id: 3,
+            previous_update_id: Some(2),
+            changes: vec![
+                LevelChange {
+                    side: Side::Bid,
+                    price: 10.,
+                    quantity: 1.,
+                },
+                LevelChange {
+                    side: Side::Ask,
+                    price: 11.,
+                    quantity: 2.,
+                },
+                LevelChange {
+                    side: Side::Bid,
+                    price: 9.,
+                    quantity: 0.,
+                },
+            ],
+            snapshot: false,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_depth_update() {
+        let update = update();
+        let depth_update = update.depth_update();
+        assert_eq!(
+            *depth_update.bids,
+            [PriceAndQuantity(9., 0.), PriceAndQuantity(10., 1.)]
+        );
+        assert_eq!(depth_update.previous_update_id, Some(2));
+        #[cfg(feature = "event-symbol")]
+        assert_eq!(depth_update.event.symbol, "XBT/USD");
+
+        let back = MarketUpdate::from_depth_update(
+            update.venue,
+            &update.symbol,
+            update.exchange_time,
+            update.receive_time,
+            &depth_update,
+            false,
+        );
+        assert_eq!(back.changes.len(), 3);
+        assert_eq!(back.depth_update(), depth_update);
+    }
+
+    #[test]
+    fn snapshot_replaces_book() {
+        let mut book = LimitOrderBook::new();
+        book.add_bid(PriceAndQuantity(1., 1.));
+        let mut snapshot = update();
+        snapshot.snapshot = true;
+        book.apply_market_update(&snapshot);
+        assert_eq!(**book.bids(), [PriceAndQuantity(10., 1.)]);
+        assert_eq!(**book.asks(), [PriceAndQuantity(11., 2.)]);
+        assert_eq!(book.update_id, 3);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let json = serde_json::to_string(&update()).unwrap();
+        assert!(json.contains(r#""venue":"kraken""#));
+        assert!(json.contains(r#""side":"bid""#));
+        assert_eq!(
+            serde_json::from_str::<MarketUpdate>(&json).unwrap(),
+            update()
+        );
+    }
+
+    #[cfg(feature = "codec")]
+    #[test]
+    fn codec_round_trip() {
+        use crate::{Decode, Encode};
+        let encoded = update().encode();
+        assert_eq!(MarketUpdate::decode(&mut &encoded[..]).unwrap(), update());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn protobuf_round_trip() {
+        use prost::Message;
+        let encoded = super::super::protos::MarketUpdate::from(update()).encode_to_vec();
+        let decoded = super::super::protos::MarketUpdate::decode(&encoded[..]).unwrap();
+        assert_eq!(MarketUpdate::try_from(decoded).unwrap(), update());
+
+        let unknown = super::super::protos::MarketUpdate {
+            venue: 42,
+            ..Default::default()
+        };
+        assert!(MarketUpdate::try_from(unknown).is_err());
+
+        let mut unspecified = super::super::protos::MarketUpdate::from(update());
+        unspecified.changes[0].side = super::super::protos::Side::Unspecified.into();
+        assert!(MarketUpdate::try_from(unspecified).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use super::{DepthUpdate, LimitOrderBook};
+use crate::ops::{update_strategies::AggregateOrCreate, PartitionPredicate, Update};
+use crate::{Asks, Bids, OrderType, PriceAndQuantity};
+use std::ops::{Deref, DerefMut};
+
+/// A trade, venue agnostic.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Execution {
+    pub price: f64,
+    pub quantity: f64,
+    /// Side of the taker. A buyer lifts the asks, a seller hits the bids.
+    pub aggressor: OrderType,
+    pub trade_id: u64,
+    /// Milliseconds since the epoch.
+    pub time: u64,
+}
+
+/// How much a level lost on a depth update and why.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Depletion {
+    /// [OrderType::Buy] for a bid, [OrderType::Sell] for an ask.
+    pub side: OrderType,
+    pub price: f64,
+    pub traded: f64,
+    pub cancelled: f64,
+}
+
+/// [LimitOrderBook] fed with trades as well as depth updates.
+/// Trades are accumulated per price until a depth update touches that price, a level that shrinks is then
+/// attributed to those trades first and to cancellations for the rest.
+/// Trades must be fed before the depth update that reflects them, which is the order venues publish them in.
+///
+/// A trade may arrive just before a depth update computed without it, so trades are kept through one depth update
+/// that doesn't touch their price and dropped on the next one. Otherwise the quantity of a trade the book never
+/// reflected, e.g. against a replenished iceberg, would be pinned on an unrelated depletion much later.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct OrderFlowBook {
+    book: LimitOrderBook,
+    // Traded quantity per price fed since the last depth update.
+    pending: Traded,
+    // Fed before the last depth update, which didn't touch their price.
+    carried: Traded,
+}
+
+#[derive(PartialEq, Clone, Debug, Default)]
+struct Traded {
+    hit_bids: Bids,
+    lifted_asks: Asks,
+}
+
+impl OrderFlowBook {
+    pub fn new(book: LimitOrderBook) -> Self {
+        Self {
+            book,
+            ..Default::default()
+        }
+    }
+
+    pub fn book(&self) -> &LimitOrderBook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> LimitOrderBook {
+        self.book
+    }
+
+    pub fn trade(&mut self, execution: &Execution) {
+        let level = PriceAndQuantity(execution.price, execution.quantity);
+        let pending = &mut self.pending;
+        match execution.aggressor {
+            OrderType::Buy => Update::<AggregateOrCreate>::process(&mut pending.lifted_asks, level),
+            OrderType::Sell => Update::<AggregateOrCreate>::process(&mut pending.hit_bids, level),
+        }
+    }
+
+    /// Applies the update to the book, returns the levels it depleted.
+    /// Trades carried over from before the previous update that this one doesn't touch are dropped.
+    pub fn apply(&mut self, update: &DepthUpdate) -> Vec<Depletion> {
+        let mut depletions = Vec::new();
+        attribute(
+            OrderType::Buy,
+            &self.book.bids,
+            &update.bids,
+            [&mut self.pending.hit_bids, &mut self.carried.hit_bids],
+            &mut depletions,
+        );
+        attribute(
+            OrderType::Sell,
+            &self.book.asks,
+            &update.asks,
+            [&mut self.pending.lifted_asks, &mut self.carried.lifted_asks],
+            &mut depletions,
+        );
+        self.book.apply(update);
+        self.carried = std::mem::take(&mut self.pending);
+        depletions
+    }
+}
+
+fn position<T>(side: &T, price: f64) -> Option<usize>
+where
+    T: PartitionPredicate + Deref<Target = Vec<PriceAndQuantity<f64, f64>>>,
+{
+    let index = side.partition_point(|level| T::partition_predicate(&level.0, &price));
+    side.get(index)
+        .is_some_and(|level| level.0 == price)
+        .then_some(index)
+}
+
+/// Compares each updated level with the book, trades at a touched price are consumed whether or not the level shrank.
+fn attribute<T>(
+    side: OrderType,
+    book: &T,
+    update: &T,
+    mut traded: [&mut T; 2],
+    depletions: &mut Vec<Depletion>,
+) where
+    T: PartitionPredicate + DerefMut<Target = Vec<PriceAndQuantity<f64, f64>>>,
+{
+    for level in update.iter() {
+        let before = position(book, level.0).map_or(0., |i| book[i].1);
+        let traded_quantity: f64 = traded
+            .iter_mut()
+            .map(|traded| position(&**traded, level.0).map_or(0., |i| traded.remove(i).1))
+            .sum();
+        let depleted = before - level.1;
+        if depleted > 0. {
+            let by_trades = depleted.min(traded_quantity);
+            depletions.push(Depletion {
+                side,
+                price: level.0,
+                traded: by_trades,
+                cancelled: depleted - by_trades,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn execution(price: f64, quantity: f64, aggressor: OrderType) -> Execution {
+        Execution {
+            price,
+            quantity,
+            aggressor,
+            trade_id: 1,
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn splits_trades_and_cancellations() {
+        let mut book = OrderFlowBook::new(LimitOrderBook::from_sides(
+            1,
+            vec![PriceAndQuantity(9., 5.), PriceAndQuantity(10., 5.)].into(),
+            vec![PriceAndQuantity(11., 5.)].into(),
+        ));
+        book.trade(&execution(10., 2., OrderType::Sell));
+        book.trade(&execution(10., 1., OrderType::Sell));
+        book.trade(&execution(11., 5., OrderType::Buy));
+
+        let depletions = book.apply(&DepthUpdate {
+            first_update_id: 2,
+            last_update_id: 2,
+            bids: vec![PriceAndQuantity(9., 4.), PriceAndQuantity(10., 1.)].into(),
+            asks: vec![PriceAndQuantity(11., 0.)].into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            depletions,
+            [
+                Depletion {
+                    side: OrderType::Buy,
+                    price: 9.,
+                    traded: 0.,
+                    cancelled: 1.,
+                },
+                Depletion {
+                    side: OrderType::Buy,
+                    price: 10.,
+                    traded: 3.,
+                    cancelled: 1.,
+                },
+                Depletion {
+                    side: OrderType::Sell,
+                    price: 11.,
+                    traded: 5.,
+                    cancelled: 0.,
+                },
+            ]
+        );
+        assert_eq!(
+            **book.book().bids(),
+            [PriceAndQuantity(9., 4.), PriceAndQuantity(10., 1.)]
+        );
+        assert!(book.book().asks().is_empty());
+    }
+
+    #[test]
+    fn trades_wait_for_their_price() {
+        let mut book = OrderFlowBook::new(LimitOrderBook::from_sides(
+            1,
+            vec![PriceAndQuantity(9., 5.), PriceAndQuantity(10., 5.)].into(),
+            Asks::new(),
+        ));
+        book.trade(&execution(10., 2., OrderType::Sell));
+
+        let untouched = DepthUpdate {
+            bids: vec![PriceAndQuantity(9., 6.)].into(),
+            ..Default::default()
+        };
+        assert!(book.apply(&untouched).is_empty());
+
+        let depletions = book.apply(&DepthUpdate {
+            bids: vec![PriceAndQuantity(10., 3.)].into(),
+            ..Default::default()
+        });
+        assert_eq!(depletions[0].traded, 2.);
+        assert_eq!(depletions[0].cancelled, 0.);
+    }
+
+    #[test]
+    fn unreflected_trades_expire() {
+        let mut book = OrderFlowBook::new(LimitOrderBook::from_sides(
+            1,
+            vec![PriceAndQuantity(9., 5.), PriceAndQuantity(10., 5.)].into(),
+            Asks::new(),
+        ));
+        book.trade(&execution(10., 2., OrderType::Sell));
+        book.trade(&execution(12., 1., OrderType::Buy));
+
+        let untouched = DepthUpdate {
+            bids: vec![PriceAndQuantity(9., 6.)].into(),
+            ..Default::default()
+        };
+        book.apply(&untouched);
+        book.apply(&untouched);
+        assert_eq!(book.pending, Traded::default());
+        assert_eq!(book.carried, Traded::default());
+
+        // The later depletion at the trade's price is a cancellation.
+        let depletions = book.apply(&DepthUpdate {
+            bids: vec![PriceAndQuantity(10., 3.)].into(),
+            ..Default::default()
+        });
+        assert_eq!(depletions[0].traded, 0.);
+        assert_eq!(depletions[0].cancelled, 2.);
+    }
+}
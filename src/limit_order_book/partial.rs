@@ -0,0 +1,192 @@
+use super::LimitOrderBook;
+use crate::ops::{update_strategies::ReplaceOrRemove, Update};
+use crate::price_and_quantity::from_str_or_number;
+use crate::{Asks, Bids, PriceAndQuantity};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// `<symbol>@depth<levels>` stream, the top levels of the book as of `last_update_id`.
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct PartialDepth {
+    #[serde(alias = "lastUpdateId", alias = "u")]
+    pub last_update_id: u64,
+    #[serde(alias = "b")]
+    pub bids: Bids,
+    #[serde(alias = "a")]
+    pub asks: Asks,
+}
+
+/// `<symbol>@bookTicker` stream, best bid and ask as of the order book `update_id`.
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct BookTicker {
+    #[serde(alias = "u")]
+    pub update_id: u64,
+    #[serde(alias = "s")]
+    pub symbol: String,
+    #[serde(alias = "b", deserialize_with = "from_str_or_number")]
+    pub bid_price: f64,
+    #[serde(alias = "B", deserialize_with = "from_str_or_number")]
+    pub bid_quantity: f64,
+    #[serde(alias = "a", deserialize_with = "from_str_or_number")]
+    pub ask_price: f64,
+    #[serde(alias = "A", deserialize_with = "from_str_or_number")]
+    pub ask_quantity: f64,
+}
+
+impl BookTicker {
+    pub fn best_bid(&self) -> PriceAndQuantity<f64, f64> {
+        PriceAndQuantity(self.bid_price, self.bid_quantity)
+    }
+
+    pub fn best_ask(&self) -> PriceAndQuantity<f64, f64> {
+        PriceAndQuantity(self.ask_price, self.ask_quantity)
+    }
+}
+
+impl LimitOrderBook {
+    /// Replaces both sides with the partial depth. Returns false, leaving the book untouched, if it is older than the book.
+    pub fn apply_partial_depth(&mut self, depth: PartialDepth) -> bool {
+        if depth.last_update_id < self.update_id {
+            return false;
+        }
+        self.bids = depth.bids;
+        self.asks = depth.asks;
+        self.update_id = depth.last_update_id;
+        if let Some(limit) = &self.depth_limit {
+            self.set_depth_limit(limit.levels);
+        }
+        true
+    }
+
+    /// Overwrites the best level of each side and drops the levels that would cross it.
+    /// The rest of the book and its `update_id` are left as they are. Returns false if the ticker is older than the book.
+    pub fn apply_book_ticker(&mut self, ticker: &BookTicker) -> bool {
+        if ticker.update_id < self.update_id {
+            return false;
+        }
+        while self.bids.last().is_some_and(|bid| bid.0 > ticker.bid_price) {
+            self.bids.pop();
+        }
+        while self.asks.last().is_some_and(|ask| ask.0 < ticker.ask_price) {
+            self.asks.pop();
+        }
+        Update::<ReplaceOrRemove>::process(&mut self.bids, ticker.best_bid());
+        Update::<ReplaceOrRemove>::process(&mut self.asks, ticker.best_ask());
+        true
+    }
+
+    /// Compares the top of the book with a ticker taken at the same update id, [None] if the ids differ.
+    pub fn matches_book_ticker(&self, ticker: &BookTicker) -> Option<bool> {
+        (ticker.update_id == self.update_id).then(|| {
+            self.bids.last() == Some(&ticker.best_bid())
+                && self.asks.last() == Some(&ticker.best_ask())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_partial_depth() {
+        let depth = r#"
+            {
+                "lastUpdateId": 160,
+                "bids": [
+                    [
+                        "0.0024",
+                        "10"
+                    ],
+                    [
+                        "0.0023",
+                        "5"
+                    ]
+                ],
+                "asks": [
+                    [
+                        "0.0026",
+                        "100"
+                    ]
+                ]
+            }
+        "#;
+        let depth: PartialDepth = serde_json::from_str(depth).unwrap();
+        let expected = PartialDepth {
+            last_update_id: 160,
+            bids: vec![PriceAndQuantity(0.0023, 5.), PriceAndQuantity(0.0024, 10.)].into(),
+            asks: vec![PriceAndQuantity(0.0026, 100.)].into(),
+        };
+        assert_eq!(depth, expected);
+
+        let mut book = LimitOrderBook::new();
+        book.add_bid(PriceAndQuantity(0.001, 1.));
+        assert!(book.apply_partial_depth(depth.clone()));
+        assert_eq!(book.bids(), &depth.bids);
+        assert_eq!(book.update_id, 160);
+
+        book.update_id = 161;
+        assert!(!book.apply_partial_depth(PartialDepth::default()));
+        assert_eq!(book.bids(), &depth.bids);
+    }
+
+    #[test]
+    fn deserialize_book_ticker() {
+        let ticker = r#"
+            {
+                "u": 400900217,
+                "s": "BNBUSDT",
+                "b": "25.35190000",
+                "B": "31.21000000",
+                "a": "25.36520000",
+                "A": "40.66000000"
+            }
+        "#;
+        let ticker: BookTicker = serde_json::from_str(ticker).unwrap();
+        let expected = BookTicker {
+            update_id: 400900217,
+            symbol: "BNBUSDT".to_string(),
+            bid_price: 25.3519,
+            bid_quantity: 31.21,
+            ask_price: 25.3652,
+            ask_quantity: 40.66,
+        };
+        assert_eq!(ticker, expected);
+    }
+
+    #[test]
+    fn book_ticker_overwrites_top() {
+        let mut book = LimitOrderBook::new();
+        book.update_id = 10;
+        for bid in [1., 2., 3.] {
+            book.add_bid(PriceAndQuantity(bid, 1.));
+        }
+        book.add_ask(PriceAndQuantity(4., 1.));
+
+        let ticker = BookTicker {
+            update_id: 11,
+            bid_price: 2.,
+            bid_quantity: 5.,
+            ask_price: 3.5,
+            ask_quantity: 2.,
+            ..Default::default()
+        };
+        assert_eq!(book.matches_book_ticker(&ticker), None);
+        assert!(book.apply_book_ticker(&ticker));
+        assert_eq!(
+            **book.bids(),
+            [PriceAndQuantity(1., 1.), PriceAndQuantity(2., 5.)]
+        );
+        assert_eq!(
+            **book.asks(),
+            [PriceAndQuantity(4., 1.), PriceAndQuantity(3.5, 2.)]
+        );
+
+        book.update_id = 11;
+        assert_eq!(book.matches_book_ticker(&ticker), Some(true));
+        book.add_bid(PriceAndQuantity(2., 4.));
+        assert_eq!(book.matches_book_ticker(&ticker), Some(false));
+    }
+}
@@ -0,0 +1,216 @@
+use super::protos::{
+    Asks, Bids, DepthUpdate, LevelEncoding, LimitOrderBook, PriceAndQuantity, Scale, ScaledLevel,
+};
+use super::{DepthUpdate as NativeDepthUpdate, LimitOrderBook as NativeLOB};
+
+/// Past this, mantissas aren't exactly representable by a double.
+const MAX_EXACT_MANTISSA: f64 = (1u64 << 53) as f64;
+
+/// Powers of ten are exact doubles up to this one.
+const MAX_DECIMALS: i32 = 22;
+
+/// The fewest decimals representing `value`, or as many as keep its mantissa exactly representable.
+fn decimals(value: f64) -> i32 {
+    let mut decimals = 0;
+    loop {
+        let power = 10f64.powi(decimals);
+        if (value * power).round() / power == value
+            || (value * power * 10.).abs() >= MAX_EXACT_MANTISSA
+            || decimals == MAX_DECIMALS
+        {
+            return decimals;
+        }
+        decimals += 1;
+    }
+}
+
+/// `None` if the mantissa isn't exactly representable by a double or doesn't decode back to `value`.
+fn mantissa(value: f64, exponent: i32) -> Option<i64> {
+    let mantissa = (value * 10f64.powi(-exponent)).round();
+    (mantissa.abs() < MAX_EXACT_MANTISSA && self::value(mantissa as i64, exponent) == value)
+        .then_some(mantissa as i64)
+}
+
+/// The exponent of the value with the most decimals, capped so that the largest value's mantissa stays exactly
+/// representable.
+fn exponent(values: impl Iterator<Item = f64> + Clone) -> i32 {
+    let decimals = values.clone().map(decimals).max().unwrap_or(0);
+    let largest = values.map(f64::abs).fold(0., f64::max);
+    -(0..=decimals)
+        .rev()
+        .find(|&decimals| largest * 10f64.powi(decimals) < MAX_EXACT_MANTISSA)
+        .unwrap_or(0)
+}
+
+/// Dividing by an exact power of ten yields the double closest to the decimal, as parsing its string would.
+fn value(mantissa: i64, exponent: i32) -> f64 {
+    if exponent < 0 {
+        mantissa as f64 / 10f64.powi(-exponent)
+    } else {
+        mantissa as f64 * 10f64.powi(exponent)
+    }
+}
+
+/// Moves the levels of both sides to their mantissas, scaled to fit the level with the most decimals.
+/// Leaves the levels as doubles and returns `None` if one of them has no exact mantissa, e.g. is too large or has
+/// more decimals than the largest level leaves room for.
+fn scale(bids: &mut Bids, asks: &mut Asks) -> Option<Scale> {
+    let levels = bids.bids.iter().chain(&asks.asks);
+    let price_exponent = exponent(levels.clone().map(|level| level.price));
+    let quantity_exponent = exponent(levels.map(|level| level.quantity));
+    let scaled = |levels: &[PriceAndQuantity]| {
+        levels
+            .iter()
+            .map(|level| {
+                Some(ScaledLevel {
+                    price: mantissa(level.price, price_exponent)?,
+                    quantity: mantissa(level.quantity, quantity_exponent)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+    };
+    let (scaled_bids, scaled_asks) = (scaled(&bids.bids)?, scaled(&asks.asks)?);
+    bids.bids.clear();
+    asks.asks.clear();
+    bids.scaled = scaled_bids;
+    asks.scaled = scaled_asks;
+    Some(Scale {
+        price_exponent,
+        quantity_exponent,
+    })
+}
+
+/// The levels of a side, decoded from their mantissas if the message has a [Scale].
+pub(super) fn decoded(
+    levels: Vec<PriceAndQuantity>,
+    scaled: Vec<ScaledLevel>,
+    scale: Option<&Scale>,
+) -> Vec<PriceAndQuantity> {
+    let Some(scale) = scale else {
+        return levels;
+    };
+    scaled
+        .into_iter()
+        .map(|level| PriceAndQuantity {
+            price: value(level.price, scale.price_exponent),
+            quantity: value(level.quantity, scale.quantity_exponent),
+        })
+        .collect()
+}
+
+impl LimitOrderBook {
+    /// `book` with its levels in `encoding`, in doubles if they can't be scaled.
+    pub fn encode(book: NativeLOB, encoding: LevelEncoding) -> Self {
+        let mut encoded = Self::from(book);
+        if encoding == LevelEncoding::Scaled {
+            encoded.scale = scale(
+                encoded.bids.get_or_insert_default(),
+                encoded.asks.get_or_insert_default(),
+            );
+        }
+        encoded
+    }
+}
+
+impl DepthUpdate {
+    /// `update` with its levels in `encoding`, in doubles if they can't be scaled.
+    pub fn encode(update: &NativeDepthUpdate, encoding: LevelEncoding) -> Self {
+        let mut encoded = Self::from(update);
+        if encoding == LevelEncoding::Scaled {
+            encoded.scale = scale(
+                encoded.bids.get_or_insert_default(),
+                encoded.asks.get_or_insert_default(),
+            );
+        }
+        encoded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn decimals_and_mantissas() {
+        assert_eq!(decimals(0.), 0);
+        assert_eq!(decimals(42.), 0);
+        assert_eq!(decimals(0.1), 1);
+        assert_eq!(decimals(0.00012345), 8);
+        assert_eq!(decimals(63251.98765432), 8);
+        assert_eq!(mantissa(0.00012345, -8), Some(12345));
+        assert_eq!(mantissa(1e16, 0), None);
+        assert_eq!(mantissa(f64::NAN, 0), None);
+        assert_eq!(value(12345, -8), 0.00012345);
+        assert_eq!(value(-3, 2), -300.);
+    }
+
+    #[test]
+    fn scaled_round_trip() {
+        let parse = |price: &str, quantity: &str| {
+            crate::PriceAndQuantity(price.parse().unwrap(), quantity.parse().unwrap())
+        };
+        let book = NativeLOB::from_sides(
+            9,
+            vec![
+                parse("63251.98765432", "0.00012345"),
+                parse("63252.1", "12"),
+            ]
+            .into(),
+            vec![parse("63260.5", "1.23456789")].into(),
+        );
+
+        let encoded = LimitOrderBook::encode(book.clone(), LevelEncoding::Scaled);
+        assert_eq!(
+            encoded.scale,
+            Some(Scale {
+                price_exponent: -8,
+                quantity_exponent: -8,
+            })
+        );
+        assert!(encoded.bids.as_ref().unwrap().bids.is_empty());
+        assert_eq!(
+            encoded.asks.as_ref().unwrap().scaled,
+            [ScaledLevel {
+                price: 6326050000000,
+                quantity: 123456789,
+            }]
+        );
+        let decoded = LimitOrderBook::decode(&encoded.encode_to_vec()[..]).unwrap();
+        assert_eq!(NativeLOB::from(decoded), book);
+
+        let doubles = LimitOrderBook::encode(book.clone(), LevelEncoding::Double);
+        assert_eq!(doubles, LimitOrderBook::from(book));
+
+        let update = NativeDepthUpdate {
+            first_update_id: 10,
+            last_update_id: 10,
+            bids: vec![parse("63251.98765432", "0")].into(),
+            ..Default::default()
+        };
+        let encoded = DepthUpdate::encode(&update, LevelEncoding::Scaled);
+        assert!(encoded.asks.as_ref().unwrap().scaled.is_empty());
+        assert_eq!(NativeDepthUpdate::from(encoded), update);
+    }
+
+    #[test]
+    fn inexact_levels_fall_back_to_doubles() {
+        for (bid, ask) in [
+            // 0.1 + 0.2 has no exact decimal, nor the quantities of this book past 1e3 at 16 decimals.
+            ((100., 0.1 + 0.2), (101., 1e3)),
+            // 1e8 leaves room for 7 decimals, 0.00012345 needs 8.
+            ((100., 0.00012345), (101., 1e8)),
+            ((100., 1.), (1e20, 1.)),
+        ] {
+            let book = NativeLOB::from_sides(
+                1,
+                vec![crate::PriceAndQuantity(bid.0, bid.1)].into(),
+                vec![crate::PriceAndQuantity(ask.0, ask.1)].into(),
+            );
+            let encoded = LimitOrderBook::encode(book.clone(), LevelEncoding::Scaled);
+            assert_eq!(encoded.scale, None);
+            assert_eq!(encoded, LimitOrderBook::from(book.clone()));
+            assert_eq!(NativeLOB::from(encoded), book);
+        }
+    }
+}
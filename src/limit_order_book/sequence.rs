@@ -0,0 +1,131 @@
+use super::DepthUpdate;
+
+/// Where an update falls relative to the last update applied to a book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Continuity {
+    /// Can be applied.
+    Continuous,
+    /// Already reflected in the book, drop it.
+    Stale,
+    /// Updates were missed, the book needs a new snapshot.
+    Gap,
+}
+
+/// A venue's rule for chaining depth updates on top of a book.
+pub trait SequenceRule {
+    fn continuity(update: &DepthUpdate, last_book_id: u64) -> Continuity;
+}
+
+/// Binance spot, a valid update [a, b] should overlap or at least
+/// not gap between the last update id and the new update range.
+pub struct Overlap;
+
+/// Every update starts right after the last one, e.g. OKX's `prevSeqId` and Bybit's `u`.
+/// Adapters map the venue's ids so that `first_update_id` is the id following the previous update.
+pub struct Consecutive;
+
+/// Binance USDⓈ-M futures, each event's `pu` is the previous event's `u`.
+/// The first event after a snapshot is the one whose range contains the snapshot's `lastUpdateId`.
+pub struct PreviousUpdateId;
+
+/// The Binance market a depth stream comes from, selects the [SequenceRule] at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Market {
+    #[default]
+    Spot,
+    UsdMFutures,
+}
+
+impl Market {
+    pub fn continuity(self, update: &DepthUpdate, last_book_id: u64) -> Continuity {
+        match self {
+            Market::Spot => Overlap::continuity(update, last_book_id),
+            Market::UsdMFutures => PreviousUpdateId::continuity(update, last_book_id),
+        }
+    }
+}
+
+impl SequenceRule for Overlap {
+    fn continuity(update: &DepthUpdate, last_book_id: u64) -> Continuity {
+        let (a, b) = (update.first_update_id, update.last_update_id);
+        if last_book_id + 1 < a {
+            Continuity::Gap
+        } else if b + 1 < last_book_id {
+            Continuity::Stale
+        } else {
+            Continuity::Continuous
+        }
+    }
+}
+
+impl SequenceRule for Consecutive {
+    fn continuity(update: &DepthUpdate, last_book_id: u64) -> Continuity {
+        let next = last_book_id + 1;
+        match update.first_update_id {
+            first if first == next => Continuity::Continuous,
+            first if first < next => Continuity::Stale,
+            _ => Continuity::Gap,
+        }
+    }
+}
+
+impl SequenceRule for PreviousUpdateId {
+    fn continuity(update: &DepthUpdate, last_book_id: u64) -> Continuity {
+        let (first, last) = (update.first_update_id, update.last_update_id);
+        if update.previous_update_id == Some(last_book_id) {
+            Continuity::Continuous
+        } else if last < last_book_id {
+            Continuity::Stale
+        } else if first <= last_book_id {
+            Continuity::Continuous
+        } else {
+            Continuity::Gap
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn consecutive() {
+        let update = DepthUpdate {
+            first_update_id: 5,
+            last_update_id: 7,
+            ..Default::default()
+        };
+        assert_eq!(update.continuity::<Consecutive>(4), Continuity::Continuous);
+        assert_eq!(update.continuity::<Consecutive>(5), Continuity::Stale);
+        assert_eq!(update.continuity::<Consecutive>(3), Continuity::Gap);
+    }
+
+    #[test]
+    fn overlap() {
+        let update = DepthUpdate {
+            first_update_id: 2,
+            last_update_id: 3,
+            ..Default::default()
+        };
+        assert_eq!(update.continuity::<Overlap>(0), Continuity::Gap);
+        assert_eq!(update.continuity::<Overlap>(2), Continuity::Continuous);
+        assert_eq!(update.continuity::<Overlap>(5), Continuity::Stale);
+    }
+
+    #[test]
+    fn previous_update_id() {
+        let update = DepthUpdate {
+            first_update_id: 157,
+            last_update_id: 160,
+            previous_update_id: Some(149),
+            ..Default::default()
+        };
+        let continuity = |last| Market::UsdMFutures.continuity(&update, last);
+        // Chained on the previous event.
+        assert_eq!(continuity(149), Continuity::Continuous);
+        // First event after a snapshot taken within its range.
+        assert_eq!(continuity(158), Continuity::Continuous);
+        assert_eq!(continuity(161), Continuity::Stale);
+        assert_eq!(continuity(150), Continuity::Gap);
+    }
+}
@@ -0,0 +1,267 @@
+//! SCALE layouts that don't depend on the `event-*` features, and a versioned envelope for stored blobs.
+//!
+//! A blob is the layout's version as a `u8` followed by the layout. Decoding an older version migrates it to the
+//! current type, so blobs keep decoding after the types change: bump the version, keep decoding the old layout.
+//! Books stored before the envelope have no version, see [LimitOrderBook::decode_unversioned].
+
+#[cfg(feature = "event")]
+use super::Event;
+use super::{DepthUpdate, LimitOrderBook};
+use crate::{Decode, Encode};
+use codec::{Error, Input, Output};
+
+/// Type stored with the version of its layout.
+pub trait Versioned: Sized {
+    /// Version of the layout [Versioned::encode_payload] writes.
+    const VERSION: u8;
+
+    fn encode_payload<T: Output + ?Sized>(&self, dest: &mut T);
+
+    /// Decodes the layout of `version`, migrating it to the current one.
+    fn decode_payload<I: Input>(version: u8, input: &mut I) -> Result<Self, Error>;
+
+    fn encode_versioned(&self) -> Vec<u8> {
+        let mut dest = vec![Self::VERSION];
+        self.encode_payload(&mut dest);
+        dest
+    }
+
+    fn decode_versioned(mut bytes: &[u8]) -> Result<Self, Error> {
+        let version = u8::decode(&mut bytes)?;
+        let decoded = Self::decode_payload(version, &mut bytes)?;
+        if !bytes.is_empty() {
+            return Err("trailing bytes after the versioned payload".into());
+        }
+        Ok(decoded)
+    }
+}
+
+fn unknown_version() -> Error {
+    "unknown layout version".into()
+}
+
+/// Every field is encoded whatever the `event-*` features, the disabled ones as defaults.
+#[cfg(feature = "event")]
+impl Encode for Event {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        #[cfg(feature = "event-id")]
+        self.id.encode_to(dest);
+        #[cfg(not(feature = "event-id"))]
+        "".encode_to(dest);
+        #[cfg(feature = "event-time")]
+        self.time.encode_to(dest);
+        #[cfg(not(feature = "event-time"))]
+        0u64.encode_to(dest);
+        #[cfg(feature = "event-symbol")]
+        self.symbol.encode_to(dest);
+        #[cfg(not(feature = "event-symbol"))]
+        "".encode_to(dest);
+    }
+}
+
+/// The fields disabled by the `event-*` features are dropped.
+#[cfg(feature = "event")]
+impl Decode for Event {
+    #[allow(unused_variables)]
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let id = String::decode(input)?;
+        let time = u64::decode(input)?;
+        let symbol = String::decode(input)?;
+        Ok(Event {
+            #[cfg(feature = "event-id")]
+            id,
+            #[cfg(feature = "event-time")]
+            time,
+            #[cfg(feature = "event-symbol")]
+            symbol,
+        })
+    }
+}
+
+#[cfg(feature = "event")]
+impl Versioned for Event {
+    const VERSION: u8 = 1;
+
+    fn encode_payload<T: Output + ?Sized>(&self, dest: &mut T) {
+        self.encode_to(dest)
+    }
+
+    fn decode_payload<I: Input>(version: u8, input: &mut I) -> Result<Self, Error> {
+        match version {
+            1 => Self::decode(input),
+            _ => Err(unknown_version()),
+        }
+    }
+}
+
+/// The event, as an [Event] layout whether or not the `event` feature is on.
+impl Encode for DepthUpdate {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        #[cfg(feature = "event")]
+        self.event.encode_to(dest);
+        #[cfg(not(feature = "event"))]
+        ("", 0u64, "").encode_to(dest);
+        self.first_update_id.encode_to(dest);
+        self.last_update_id.encode_to(dest);
+        self.previous_update_id.encode_to(dest);
+        self.transaction_time.encode_to(dest);
+        self.bids.encode_to(dest);
+        self.asks.encode_to(dest);
+    }
+}
+
+impl Decode for DepthUpdate {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        #[cfg(feature = "event")]
+        let event = Event::decode(input)?;
+        #[cfg(not(feature = "event"))]
+        <(String, u64, String)>::decode(input)?;
+        Ok(DepthUpdate {
+            #[cfg(feature = "event")]
+            event,
+            first_update_id: Decode::decode(input)?,
+            last_update_id: Decode::decode(input)?,
+            previous_update_id: Decode::decode(input)?,
+            transaction_time: Decode::decode(input)?,
+            bids: Decode::decode(input)?,
+            asks: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Versioned for DepthUpdate {
+    const VERSION: u8 = 1;
+
+    fn encode_payload<T: Output + ?Sized>(&self, dest: &mut T) {
+        self.encode_to(dest)
+    }
+
+    fn decode_payload<I: Input>(version: u8, input: &mut I) -> Result<Self, Error> {
+        match version {
+            1 => Self::decode(input),
+            _ => Err(unknown_version()),
+        }
+    }
+}
+
+/// The depth limit isn't stored, a decoded book tracks every level.
+impl Versioned for LimitOrderBook {
+    const VERSION: u8 = 1;
+
+    fn encode_payload<T: Output + ?Sized>(&self, dest: &mut T) {
+        self.encode_to(dest)
+    }
+
+    fn decode_payload<I: Input>(version: u8, input: &mut I) -> Result<Self, Error> {
+        match version {
+            1 => Self::decode(input),
+            _ => Err(unknown_version()),
+        }
+    }
+}
+
+impl LimitOrderBook {
+    /// Decodes a book encoded without the envelope, as books were before it. Such a blob is its version 1 layout.
+    pub fn decode_unversioned(mut bytes: &[u8]) -> Result<Self, Error> {
+        let book = Self::decode(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err("trailing bytes after the book".into());
+        }
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PriceAndQuantity;
+
+    fn update() -> DepthUpdate {
+        let mut update = DepthUpdate {
+            first_update_id: 5,
+            last_update_id: 6,
+            previous_update_id: Some(4),
+            bids: vec![PriceAndQuantity(1., 0.), PriceAndQuantity(1.5, 2.)].into(),
+            asks: vec![PriceAndQuantity(3., 1.)].into(),
+            ..Default::default()
+        };
+        update.transaction_time = Some(1_700_000_000_000);
+        #[cfg(feature = "event-symbol")]
+        {
+            update.event.symbol = "BNBBTC".to_owned();
+        }
+        update
+    }
+
+    #[test]
+    fn layout_ignores_event_features() {
+        let update = update();
+        #[cfg(feature = "event-symbol")]
+        let symbol = "BNBBTC";
+        #[cfg(not(feature = "event-symbol"))]
+        let symbol = "";
+        let expected = (
+            ("", 0u64, symbol),
+            5u64,
+            6u64,
+            Some(4u64),
+            Some(1_700_000_000_000u64),
+            update.bids.clone(),
+            update.asks.clone(),
+        )
+            .encode();
+        assert_eq!(update.encode(), expected);
+        assert_eq!(DepthUpdate::decode(&mut &expected[..]), Ok(update));
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let update = update();
+        let blob = update.encode_versioned();
+        assert_eq!(blob[0], 1);
+        assert_eq!(DepthUpdate::decode_versioned(&blob), Ok(update));
+
+        let book = LimitOrderBook::from_sides(
+            7,
+            vec![PriceAndQuantity(1., 2.)].into(),
+            vec![PriceAndQuantity(3., 4.)].into(),
+        );
+        assert_eq!(
+            LimitOrderBook::decode_versioned(&book.encode_versioned()),
+            Ok(book)
+        );
+
+        #[cfg(feature = "event")]
+        {
+            let event = Event::default();
+            assert_eq!(
+                Event::decode_versioned(&event.encode_versioned()),
+                Ok(event)
+            );
+        }
+    }
+
+    #[test]
+    fn reads_unversioned_books() {
+        let book = LimitOrderBook::from_sides(
+            7,
+            vec![PriceAndQuantity(1., 2.)].into(),
+            vec![PriceAndQuantity(3., 4.)].into(),
+        );
+        let mut blob = (7u64, book.bids().clone(), book.asks().clone()).encode();
+        assert_eq!(book.encode(), blob);
+        assert_eq!(LimitOrderBook::decode_unversioned(&blob), Ok(book));
+        blob.push(0);
+        assert!(LimitOrderBook::decode_unversioned(&blob).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let mut blob = update().encode_versioned();
+        blob[0] = 9;
+        assert!(DepthUpdate::decode_versioned(&blob).is_err());
+        let mut trailing = update().encode_versioned();
+        trailing.push(0);
+        assert!(DepthUpdate::decode_versioned(&trailing).is_err());
+    }
+}
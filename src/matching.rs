@@ -0,0 +1,576 @@
+//! Price-time priority matching of limit [Order]s, e.g. to run a mock exchange.
+
+use crate::ops::update_side;
+use crate::{DepthUpdate, LimitOrderBook, Order, OrderType, PriceAndQuantity};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+pub type OrderId = u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionType {
+    /// The order was accepted, reported before its trades.
+    New,
+    Trade,
+    Cancelled,
+    Amended,
+}
+
+/// What happened to an order, the makers of a trade get a report of their own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExecutionReport {
+    pub order_id: OrderId,
+    pub order_type: OrderType,
+    pub execution_type: ExecutionType,
+    /// Trade price of a trade, limit price otherwise.
+    pub price: f64,
+    /// Traded quantity of a trade, quantity of the order otherwise.
+    pub quantity: f64,
+    /// Quantity left to trade, resting on the book unless cancelled.
+    pub leaves_quantity: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderError {
+    UnknownOrder(OrderId),
+    InvalidPrice(f64),
+    InvalidQuantity(f64),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::UnknownOrder(id) => write!(f, "no resting order {id}"),
+            OrderError::InvalidPrice(price) => write!(f, "invalid price {price}"),
+            OrderError::InvalidQuantity(quantity) => write!(f, "invalid quantity {quantity}"),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+/// Reports of an operation in the order they happened, with the change it made to the book.
+/// `update` is `None` if the book didn't change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outcome {
+    pub reports: Vec<ExecutionReport>,
+    pub update: Option<DepthUpdate>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Resting {
+    id: OrderId,
+    quantity: f64,
+}
+
+/// Orders resting at a price, first in first out.
+#[derive(Clone, Debug, PartialEq)]
+struct Level {
+    price: f64,
+    orders: VecDeque<Resting>,
+}
+
+impl Level {
+    fn quantity(&self) -> f64 {
+        self.orders.iter().map(|order| order.quantity).sum()
+    }
+}
+
+/// Levels touched by an operation, looked up once it is done to build its [DepthUpdate].
+#[derive(Default)]
+struct Changes {
+    reports: Vec<ExecutionReport>,
+    bids: Vec<f64>,
+    asks: Vec<f64>,
+}
+
+impl Changes {
+    fn touch(&mut self, order_type: OrderType, price: f64) {
+        match order_type {
+            OrderType::Buy => self.bids.push(price),
+            OrderType::Sell => self.asks.push(price),
+        }
+    }
+}
+
+fn opposite(order_type: OrderType) -> OrderType {
+    match order_type {
+        OrderType::Buy => OrderType::Sell,
+        OrderType::Sell => OrderType::Buy,
+    }
+}
+
+/// Index of the level at `price` on the side of `order_type`, or where it would be inserted.
+fn position(levels: &[Level], order_type: OrderType, price: f64) -> Result<usize, usize> {
+    let index = levels.partition_point(|level| match order_type {
+        OrderType::Buy => level.price < price,
+        OrderType::Sell => level.price > price,
+    });
+    match levels.get(index) {
+        Some(level) if level.price == price => Ok(index),
+        _ => Err(index),
+    }
+}
+
+fn validate(price: f64, quantity: f64) -> Result<(), OrderError> {
+    if !(price.is_finite() && price > 0.) {
+        return Err(OrderError::InvalidPrice(price));
+    }
+    if !(quantity.is_finite() && quantity > 0.) {
+        return Err(OrderError::InvalidQuantity(quantity));
+    }
+    Ok(())
+}
+
+/// Limit order book of individual orders. Every operation that changes the aggregated book bumps its update id and
+/// returns the [DepthUpdate] bringing a [LimitOrderBook] replica from the previous id to the new one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchingEngine {
+    update_id: u64,
+    last_order_id: OrderId,
+    /// Ascending like [crate::Bids], best last.
+    bids: Vec<Level>,
+    /// Descending like [crate::Asks], best last.
+    asks: Vec<Level>,
+    /// Side and price of the resting orders.
+    orders: HashMap<OrderId, (OrderType, f64)>,
+}
+
+impl MatchingEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update_id(&self) -> u64 {
+        self.update_id
+    }
+
+    /// The aggregated book, as of [MatchingEngine::update_id].
+    pub fn book(&self) -> LimitOrderBook {
+        let side = |levels: &[Level]| {
+            levels
+                .iter()
+                .map(|level| PriceAndQuantity(level.price, level.quantity()))
+                .collect::<Vec<_>>()
+        };
+        LimitOrderBook::from_sides(
+            self.update_id,
+            side(&self.bids).into(),
+            side(&self.asks).into(),
+        )
+    }
+
+    /// Side, price and quantity left of a resting order.
+    pub fn order(&self, id: OrderId) -> Option<Order<f64, f64>> {
+        let &(order_type, price) = self.orders.get(&id)?;
+        let levels = self.side(order_type);
+        let level = &levels[position(levels, order_type, price).ok()?];
+        let resting = level.orders.iter().find(|order| order.id == id)?;
+        Some(Order::new(
+            PriceAndQuantity(price, resting.quantity),
+            order_type,
+        ))
+    }
+
+    /// Matches `order` against the other side, what is left rests on the book. The new order's id is in the
+    /// [ExecutionType::New] report that comes first.
+    pub fn submit(&mut self, order: &Order<f64, f64>) -> Result<Outcome, OrderError> {
+        let &PriceAndQuantity(price, quantity) = order.price_quantity();
+        validate(price, quantity)?;
+        self.last_order_id += 1;
+        let id = self.last_order_id;
+        let mut changes = Changes::default();
+        changes.reports.push(ExecutionReport {
+            order_id: id,
+            order_type: order.order_type(),
+            execution_type: ExecutionType::New,
+            price,
+            quantity,
+            leaves_quantity: quantity,
+        });
+        self.place(id, order.order_type(), price, quantity, &mut changes);
+        Ok(self.finish(changes))
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Result<Outcome, OrderError> {
+        let mut changes = Changes::default();
+        let (order_type, price, quantity) = self.take(id, &mut changes)?;
+        changes.reports.push(ExecutionReport {
+            order_id: id,
+            order_type,
+            execution_type: ExecutionType::Cancelled,
+            price,
+            quantity,
+            leaves_quantity: 0.,
+        });
+        Ok(self.finish(changes))
+    }
+
+    /// Sets the price and the quantity left of a resting order. Reducing the quantity at the same price keeps the
+    /// order's time priority, otherwise it goes to the back of the queue and may trade at its new price.
+    pub fn amend(&mut self, id: OrderId, price: f64, quantity: f64) -> Result<Outcome, OrderError> {
+        validate(price, quantity)?;
+        let &(order_type, resting_price) =
+            self.orders.get(&id).ok_or(OrderError::UnknownOrder(id))?;
+        let mut changes = Changes::default();
+        changes.reports.push(ExecutionReport {
+            order_id: id,
+            order_type,
+            execution_type: ExecutionType::Amended,
+            price,
+            quantity,
+            leaves_quantity: quantity,
+        });
+
+        if price == resting_price {
+            let levels = self.side_mut(order_type);
+            let index = position(levels, order_type, price).expect("resting orders have a level");
+            let resting = levels[index]
+                .orders
+                .iter_mut()
+                .find(|order| order.id == id)
+                .expect("resting orders are on their level");
+            if quantity <= resting.quantity {
+                resting.quantity = quantity;
+                changes.touch(order_type, price);
+                return Ok(self.finish(changes));
+            }
+        }
+        self.take(id, &mut changes)?;
+        self.place(id, order_type, price, quantity, &mut changes);
+        Ok(self.finish(changes))
+    }
+
+    fn side(&self, order_type: OrderType) -> &Vec<Level> {
+        match order_type {
+            OrderType::Buy => &self.bids,
+            OrderType::Sell => &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, order_type: OrderType) -> &mut Vec<Level> {
+        match order_type {
+            OrderType::Buy => &mut self.bids,
+            OrderType::Sell => &mut self.asks,
+        }
+    }
+
+    /// Trades `quantity` against the best levels of the other side, then rests what is left.
+    fn place(
+        &mut self,
+        id: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: f64,
+        changes: &mut Changes,
+    ) {
+        let maker_type = opposite(order_type);
+        let crosses = |level: &Level| match order_type {
+            OrderType::Buy => level.price <= price,
+            OrderType::Sell => level.price >= price,
+        };
+        let mut leaves = quantity;
+        let (makers, orders) = match maker_type {
+            OrderType::Buy => (&mut self.bids, &mut self.orders),
+            OrderType::Sell => (&mut self.asks, &mut self.orders),
+        };
+        while leaves > 0. {
+            let Some(level) = makers.last_mut().filter(|level| crosses(level)) else {
+                break;
+            };
+            changes.touch(maker_type, level.price);
+            while leaves > 0. {
+                let Some(maker) = level.orders.front_mut() else {
+                    break;
+                };
+                let traded = maker.quantity.min(leaves);
+                maker.quantity -= traded;
+                leaves -= traded;
+                let maker = maker.clone();
+                let trade = ExecutionReport {
+                    order_id: id,
+                    order_type,
+                    execution_type: ExecutionType::Trade,
+                    price: level.price,
+                    quantity: traded,
+                    leaves_quantity: leaves,
+                };
+                changes.reports.push(trade);
+                changes.reports.push(ExecutionReport {
+                    order_id: maker.id,
+                    order_type: maker_type,
+                    leaves_quantity: maker.quantity,
+                    ..trade
+                });
+                if maker.quantity <= 0. {
+                    orders.remove(&maker.id);
+                    level.orders.pop_front();
+                }
+            }
+            if level.orders.is_empty() {
+                makers.pop();
+            }
+        }
+
+        if leaves > 0. {
+            let levels = self.side_mut(order_type);
+            let resting = Resting {
+                id,
+                quantity: leaves,
+            };
+            match position(levels, order_type, price) {
+                Ok(index) => levels[index].orders.push_back(resting),
+                Err(index) => levels.insert(
+                    index,
+                    Level {
+                        price,
+                        orders: VecDeque::from([resting]),
+                    },
+                ),
+            }
+            self.orders.insert(id, (order_type, price));
+            changes.touch(order_type, price);
+        }
+    }
+
+    /// Removes a resting order from the book, returning its side, price and quantity left.
+    fn take(
+        &mut self,
+        id: OrderId,
+        changes: &mut Changes,
+    ) -> Result<(OrderType, f64, f64), OrderError> {
+        let (order_type, price) = self
+            .orders
+            .remove(&id)
+            .ok_or(OrderError::UnknownOrder(id))?;
+        let levels = self.side_mut(order_type);
+        let index = position(levels, order_type, price).expect("resting orders have a level");
+        let level = &mut levels[index];
+        let at = level
+            .orders
+            .iter()
+            .position(|order| order.id == id)
+            .expect("resting orders are on their level");
+        let resting = level.orders.remove(at).expect("position is in bounds");
+        if level.orders.is_empty() {
+            levels.remove(index);
+        }
+        changes.touch(order_type, price);
+        Ok((order_type, price, resting.quantity))
+    }
+
+    fn finish(&mut self, changes: Changes) -> Outcome {
+        let Changes {
+            reports,
+            bids,
+            asks,
+        } = changes;
+        if bids.is_empty() && asks.is_empty() {
+            return Outcome {
+                reports,
+                update: None,
+            };
+        }
+        let levels = |order_type: OrderType, prices: Vec<f64>| {
+            let levels = self.side(order_type);
+            prices
+                .into_iter()
+                .map(|price| {
+                    let quantity = position(levels, order_type, price)
+                        .map_or(0., |index| levels[index].quantity());
+                    PriceAndQuantity(price, quantity)
+                })
+                .collect()
+        };
+        let bids = update_side(levels(OrderType::Buy, bids));
+        let asks = update_side(levels(OrderType::Sell, asks));
+        self.update_id += 1;
+        Outcome {
+            reports,
+            update: Some(DepthUpdate {
+                first_update_id: self.update_id,
+                last_update_id: self.update_id,
+                bids,
+                asks,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "grpc")]
+mod protos {
+    use super::{ExecutionType, OrderType};
+    use crate::limit_order_book::protos;
+    use prost::UnknownEnumValue;
+
+    impl From<OrderType> for protos::OrderType {
+        fn from(order_type: OrderType) -> Self {
+            match order_type {
+                OrderType::Buy => protos::OrderType::Buy,
+                OrderType::Sell => protos::OrderType::Sell,
+            }
+        }
+    }
+
+    impl TryFrom<i32> for OrderType {
+        type Error = UnknownEnumValue;
+
+        fn try_from(value: i32) -> Result<Self, Self::Error> {
+            match protos::OrderType::try_from(value)? {
+                protos::OrderType::Unspecified => Err(UnknownEnumValue(value)),
+                protos::OrderType::Buy => Ok(OrderType::Buy),
+                protos::OrderType::Sell => Ok(OrderType::Sell),
+            }
+        }
+    }
+
+    impl From<ExecutionType> for protos::ExecutionType {
+        fn from(execution_type: ExecutionType) -> Self {
+            match execution_type {
+                ExecutionType::New => protos::ExecutionType::New,
+                ExecutionType::Trade => protos::ExecutionType::Trade,
+                ExecutionType::Cancelled => protos::ExecutionType::Cancelled,
+                ExecutionType::Amended => protos::ExecutionType::Amended,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn order(price: f64, quantity: f64, order_type: OrderType) -> Order<f64, f64> {
+        Order::new(PriceAndQuantity(price, quantity), order_type)
+    }
+
+    /// Applies the update of `outcome` to `replica`, checking it is the next one.
+    fn replicate(replica: &mut LimitOrderBook, outcome: &Outcome) {
+        let update = outcome.update.as_ref().unwrap();
+        assert_eq!(update.first_update_id, replica.update_id + 1);
+        replica.apply(update);
+    }
+
+    fn new_order_id(outcome: &Outcome) -> OrderId {
+        assert_eq!(outcome.reports[0].execution_type, ExecutionType::New);
+        outcome.reports[0].order_id
+    }
+
+    #[test]
+    fn matches_by_price_then_time() {
+        let mut engine = MatchingEngine::new();
+        let mut replica = LimitOrderBook::new();
+        let mut makers = Vec::new();
+        for (price, quantity) in [(101., 1.), (100., 2.), (100., 3.)] {
+            let outcome = engine
+                .submit(&order(price, quantity, OrderType::Sell))
+                .unwrap();
+            assert_eq!(outcome.reports.len(), 1);
+            replicate(&mut replica, &outcome);
+            makers.push(new_order_id(&outcome));
+        }
+        assert_eq!(replica, engine.book());
+        assert_eq!(
+            **engine.book().asks(),
+            [PriceAndQuantity(101., 1.), PriceAndQuantity(100., 5.)]
+        );
+
+        let outcome = engine.submit(&order(101., 4., OrderType::Buy)).unwrap();
+        let taker = new_order_id(&outcome);
+        let trades: Vec<_> = outcome.reports[1..]
+            .iter()
+            .map(|report| {
+                (
+                    report.order_id,
+                    report.price,
+                    report.quantity,
+                    report.leaves_quantity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            trades,
+            [
+                (taker, 100., 2., 2.),
+                (makers[1], 100., 2., 0.),
+                (taker, 100., 2., 0.),
+                (makers[2], 100., 2., 1.),
+            ]
+        );
+        replicate(&mut replica, &outcome);
+        assert_eq!(replica, engine.book());
+        assert_eq!(
+            **engine.book().asks(),
+            [PriceAndQuantity(101., 1.), PriceAndQuantity(100., 1.)]
+        );
+        assert!(engine.book().bids().is_empty());
+        assert_eq!(engine.order(makers[1]), None);
+        assert_eq!(
+            engine.order(makers[2]),
+            Some(order(100., 1., OrderType::Sell))
+        );
+
+        // Sweeps both levels, the rest of the bid rests on the book.
+        let outcome = engine.submit(&order(102., 3., OrderType::Buy)).unwrap();
+        replicate(&mut replica, &outcome);
+        assert!(engine.book().asks().is_empty());
+        assert_eq!(**engine.book().bids(), [PriceAndQuantity(102., 1.)]);
+        assert_eq!(replica, engine.book());
+    }
+
+    #[test]
+    fn cancel_and_amend() {
+        let mut engine = MatchingEngine::new();
+        let mut replica = LimitOrderBook::new();
+        let mut bids = Vec::new();
+        for quantity in [1., 2.] {
+            let outcome = engine
+                .submit(&order(99., quantity, OrderType::Buy))
+                .unwrap();
+            replicate(&mut replica, &outcome);
+            bids.push(new_order_id(&outcome));
+        }
+
+        // Reducing keeps the priority, growing loses it.
+        let outcome = engine.amend(bids[0], 99., 0.5).unwrap();
+        replicate(&mut replica, &outcome);
+        assert_eq!(**engine.book().bids(), [PriceAndQuantity(99., 2.5)]);
+        let outcome = engine.submit(&order(99., 0.5, OrderType::Sell)).unwrap();
+        replicate(&mut replica, &outcome);
+        assert_eq!(engine.order(bids[0]), None);
+
+        let outcome = engine.amend(bids[1], 98., 2.).unwrap();
+        replicate(&mut replica, &outcome);
+        assert_eq!(**engine.book().bids(), [PriceAndQuantity(98., 2.)]);
+
+        // Amending through the other side trades.
+        let outcome = engine.submit(&order(101., 1., OrderType::Sell)).unwrap();
+        replicate(&mut replica, &outcome);
+        let outcome = engine.amend(bids[1], 101., 2.).unwrap();
+        assert_eq!(outcome.reports[1].execution_type, ExecutionType::Trade);
+        replicate(&mut replica, &outcome);
+        assert_eq!(**engine.book().bids(), [PriceAndQuantity(101., 1.)]);
+        assert!(engine.book().asks().is_empty());
+
+        let outcome = engine.cancel(bids[1]).unwrap();
+        assert_eq!(outcome.reports[0].execution_type, ExecutionType::Cancelled);
+        assert_eq!(outcome.reports[0].quantity, 1.);
+        replicate(&mut replica, &outcome);
+        assert!(engine.book().bids().is_empty());
+        assert_eq!(replica, engine.book());
+
+        assert_eq!(
+            engine.cancel(bids[1]),
+            Err(OrderError::UnknownOrder(bids[1]))
+        );
+        assert_eq!(
+            engine.submit(&order(0., 1., OrderType::Buy)),
+            Err(OrderError::InvalidPrice(0.))
+        );
+        assert_eq!(
+            engine.amend(bids[1], 1., f64::NAN).unwrap_err().to_string(),
+            "invalid quantity NaN"
+        );
+    }
+}
@@ -5,8 +5,12 @@ use crate::{
     PriceAndQuantity,
 };
 use core::ops::DerefMut;
+use std::cmp::Ordering;
 use std::ops::Add;
 
+#[cfg(feature = "serde")]
+pub mod seed;
+
 pub trait PartitionPredicate {
     /// Defines an ordering for the binary search partition.
     fn partition_predicate<P: PartialOrd>(lhs: &P, rhs: &P) -> bool;
@@ -184,4 +188,187 @@ where
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Applies a batch of levels in a single pass instead of one binary search and `Vec` shift per level.
+/// The result is the same as calling [Update::process] on every level in iteration order.
+pub trait Merge<S: Strategy>: Update<S> {
+    fn merge<I: IntoIterator<Item = Self::Level>>(&mut self, levels: I);
+}
+
+/// Total order of two prices as laid out by `T`.
+pub(crate) fn side_order<T: PartitionPredicate, P: PartialOrd>(lhs: &P, rhs: &P) -> Ordering {
+    if T::partition_predicate(lhs, rhs) {
+        Ordering::Less
+    } else if T::partition_predicate(rhs, lhs) {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// Builds one side of an update from levels in arrival order.
+/// Levels are sorted in the side's order, on repeated prices the last one wins and zero quantities are kept as removals.
+pub(crate) fn update_side<T>(mut levels: Vec<PriceAndQuantity<f64, f64>>) -> T
+where
+    T: PartitionPredicate + From<Vec<PriceAndQuantity<f64, f64>>>,
+{
+    // The sort is stable, reversing first leaves the latest level at the head of each run of equal prices.
+    levels.reverse();
+    levels.sort_by(|lhs, rhs| side_order::<T, f64>(&lhs.0, &rhs.0));
+    levels.dedup_by(|later, first| later.0 == first.0);
+    levels.into()
+}
+
+fn merge_with<T, P, Q, I, F>(side: &mut T, levels: I, fold: F)
+where
+    T: PartitionPredicate + DerefMut<Target = Vec<PriceAndQuantity<P, Q>>>,
+    P: PartialOrd,
+    I: IntoIterator<Item = PriceAndQuantity<P, Q>>,
+    F: Fn(Option<PriceAndQuantity<P, Q>>, PriceAndQuantity<P, Q>) -> Option<PriceAndQuantity<P, Q>>,
+{
+    let mut updates: Vec<_> = levels.into_iter().collect();
+    // Stable, so levels at the same price keep their arrival order.
+    updates.sort_by(|lhs, rhs| side_order::<T, P>(&lhs.0, &rhs.0));
+
+    let old = std::mem::take(&mut **side);
+    let mut merged = Vec::with_capacity(old.len() + updates.len());
+    let mut old = old.into_iter().peekable();
+    let mut updates = updates.into_iter().peekable();
+
+    while let Some(new) = updates.next() {
+        while let Some(level) = old.next_if(|level| T::partition_predicate(&level.0, &new.0)) {
+            merged.push(level);
+        }
+        let mut level = old.next_if(|level| level.0 == new.0);
+        let mut new = new;
+        // Fold every update at this price onto the existing level.
+        loop {
+            let next = updates.next_if(|next| next.0 == new.0);
+            level = fold(level, new);
+            match next {
+                Some(next) => new = next,
+                None => break,
+            }
+        }
+        merged.extend(level);
+    }
+    merged.extend(old);
+
+    **side = merged;
+}
+
+impl<T, P, Q> Merge<ReplaceOrRemove> for T
+where
+    T: PartitionPredicate + DerefMut<Target = Vec<PriceAndQuantity<P, Q>>>,
+    P: PartialOrd,
+    Q: Add<Q, Output = Q> + Copy + Default + PartialEq,
+{
+    fn merge<I: IntoIterator<Item = Self::Level>>(&mut self, levels: I) {
+        merge_with(
+            self,
+            levels,
+            |existing, new| match ReplaceOrRemove::operation(&new, existing.as_ref()) {
+                ReplaceOrRemove::Replace | ReplaceOrRemove::Displace => Some(new),
+                ReplaceOrRemove::Remove => None,
+                ReplaceOrRemove::Noop => existing,
+            },
+        )
+    }
+}
+
+impl<T, P, Q> Merge<AggregateOrCreate> for T
+where
+    T: PartitionPredicate + DerefMut<Target = Vec<PriceAndQuantity<P, Q>>>,
+    P: PartialOrd,
+    Q: Add<Q, Output = Q> + Copy + Default + PartialEq,
+{
+    fn merge<I: IntoIterator<Item = Self::Level>>(&mut self, levels: I) {
+        merge_with(
+            self,
+            levels,
+            |existing, new| match AggregateOrCreate::operation(&new, existing.as_ref()) {
+                AggregateOrCreate::Aggregated => {
+                    existing.map(|old| PriceAndQuantity(old.0, old.1 + new.1))
+                }
+                AggregateOrCreate::Remove => None,
+                AggregateOrCreate::Create => Some(new),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Asks, Bids};
+    use proptest::{collection, prop_assert_eq, proptest, strategy::Strategy as _};
+
+    fn levels() -> impl proptest::strategy::Strategy<Value = Vec<PriceAndQuantity<f64, i64>>> {
+        // Few distinct prices and small quantities so that levels collide, cancel out and get removed.
+        collection::vec((0..20u8, -3..4i64), 0..60).prop_map(|v| {
+            v.into_iter()
+                .map(|(p, q)| PriceAndQuantity(p as f64, q))
+                .collect()
+        })
+    }
+
+    fn sequential<S, T>(mut side: T, levels: &[PriceAndQuantity<f64, i64>]) -> T
+    where
+        S: Strategy,
+        T: Update<S, Level = PriceAndQuantity<f64, i64>>,
+    {
+        for level in levels {
+            side.process(*level);
+        }
+        side
+    }
+
+    #[test]
+    fn update_side_keeps_last_and_removals() {
+        let bids: Bids = update_side(vec![
+            PriceAndQuantity(2., 1.),
+            PriceAndQuantity(1., 0.),
+            PriceAndQuantity(2., 3.),
+        ]);
+        assert_eq!(*bids, [PriceAndQuantity(1., 0.), PriceAndQuantity(2., 3.)]);
+    }
+
+    proptest! {
+        #[test]
+        fn replace_or_remove_bids(book in levels(), update in levels()) {
+            let book = sequential::<ReplaceOrRemove, _>(Bids::new(), &book);
+            let expected = sequential::<ReplaceOrRemove, _>(book.clone(), &update);
+            let mut merged = book;
+            Merge::<ReplaceOrRemove>::merge(&mut merged, update);
+            prop_assert_eq!(merged, expected);
+        }
+
+        #[test]
+        fn replace_or_remove_asks(book in levels(), update in levels()) {
+            let book = sequential::<ReplaceOrRemove, _>(Asks::new(), &book);
+            let expected = sequential::<ReplaceOrRemove, _>(book.clone(), &update);
+            let mut merged = book;
+            Merge::<ReplaceOrRemove>::merge(&mut merged, update);
+            prop_assert_eq!(merged, expected);
+        }
+
+        #[test]
+        fn aggregate_or_create_bids(book in levels(), update in levels()) {
+            let book = sequential::<AggregateOrCreate, _>(Bids::new(), &book);
+            let expected = sequential::<AggregateOrCreate, _>(book.clone(), &update);
+            let mut merged = book;
+            Merge::<AggregateOrCreate>::merge(&mut merged, update);
+            prop_assert_eq!(merged, expected);
+        }
+
+        #[test]
+        fn aggregate_or_create_asks(book in levels(), update in levels()) {
+            let book = sequential::<AggregateOrCreate, _>(Asks::new(), &book);
+            let expected = sequential::<AggregateOrCreate, _>(book.clone(), &update);
+            let mut merged = book;
+            Merge::<AggregateOrCreate>::merge(&mut merged, update);
+            prop_assert_eq!(merged, expected);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use super::{Strategy, Update};
+use crate::price_and_quantity::{Price, Quantity};
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Streams a sequence of levels straight into a side with the strategy `S`, no intermediate `Vec` is built.
+pub struct LevelsSeed<'a, T, S> {
+    side: &'a mut T,
+    strategy: PhantomData<S>,
+}
+
+impl<'a, T, S> LevelsSeed<'a, T, S> {
+    pub fn new(side: &'a mut T) -> Self {
+        Self {
+            side,
+            strategy: PhantomData,
+        }
+    }
+}
+
+impl<'de, T, S> DeserializeSeed<'de> for LevelsSeed<'_, T, S>
+where
+    S: Strategy,
+    T: Update<S>,
+    T::Level: Deserialize<'de>,
+    <T::Level as Price>::P: PartialOrd,
+    <T::Level as Quantity>::Q: Default + PartialEq,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T, S> Visitor<'de> for LevelsSeed<'_, T, S>
+where
+    S: Strategy,
+    T: Update<S>,
+    T::Level: Deserialize<'de>,
+    <T::Level as Price>::P: PartialOrd,
+    <T::Level as Quantity>::Q: Default + PartialEq,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of price levels")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(level) = seq.next_element()? {
+            self.side.process(level);
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,17 @@
 #[cfg(feature = "serde")]
-use serde::{de, de::Visitor, Deserialize, Deserializer, Serialize};
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::fmt::{self, Display};
 use std::{marker::PhantomData, ops::Add, str::FromStr};
 
 #[cfg_attr(feature = "codec", derive(crate::Encode, crate::Decode))]
-#[cfg_attr(feature = "serde", derive(Deserialize), derive(Serialize))]
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 ///Careful, this struct manually implements [Add] which in this context, is not commutative. It adds quantity while copying the rhs' price.
 /// If prices are not equal, it is also not associative; Adding quantities from different price levels is not a sound operation.
-pub struct PriceAndQuantity<P, Q>(
-    #[serde(deserialize_with = "de_from_str")]
-    #[serde(bound(deserialize = "P: FromStr, P::Err: Display"))]
-    pub P,
-    #[serde(deserialize_with = "de_from_str")]
-    #[serde(bound(deserialize = "Q: FromStr, Q::Err: Display"))]
-    pub Q,
-);
+pub struct PriceAndQuantity<P, Q>(pub P, pub Q);
 
 impl<P, Q> Display for PriceAndQuantity<P, Q>
 where
@@ -72,29 +68,184 @@ where
     }
 }
 
-fn de_from_str<'de, D, T: FromStr>(deserializer: D) -> Result<T, D::Error>
+/// Serializes as `["price", "quantity"]`, the string encoded shape the exchange sends.
+#[cfg(feature = "serde")]
+impl<P, Q> Serialize for PriceAndQuantity<P, Q>
+where
+    P: Display,
+    Q: Display,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut level = serializer.serialize_tuple(2)?;
+        level.serialize_element(&AsStr(&self.0))?;
+        level.serialize_element(&AsStr(&self.1))?;
+        level.end()
+    }
+}
+
+struct AsStr<'a, T>(&'a T);
+
+impl<T: Display> Serialize for AsStr<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self.0)
+    }
+}
+
+/// `deserialize_with` helper for a number sent either as a string or as a JSON number.
+#[cfg(feature = "serde")]
+pub(crate) fn from_str_or_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
+    T: FromStr,
     T::Err: Display,
 {
-    struct NumberFromStr<V>(PhantomData<V>);
+    FromStrSeed(PhantomData).deserialize(deserializer)
+}
 
-    impl<'de, Error: Display, V: FromStr<Err = Error>> Visitor<'de> for NumberFromStr<V> {
-        type Value = V;
+/// Parses a number with [FromStr] whether it was sent as a string or as a JSON number.
+struct FromStrSeed<V>(PhantomData<V>);
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str(r#"a string containing "float" or "number""#)
-        }
+impl<'de, V> DeserializeSeed<'de> for FromStrSeed<V>
+where
+    V: FromStr,
+    V::Err: Display,
+{
+    type Value = V;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, V> Visitor<'de> for FromStrSeed<V>
+where
+    V: FromStr,
+    V::Err: Display,
+{
+    type Value = V;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(r#"a number or a string containing "float" or "number""#)
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Self::Value::from_str(s).map_err(de::Error::custom)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&v.to_string())
+    }
 
-        fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            Self::Value::from_str(s).map_err(de::Error::custom)
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&v.to_string())
+    }
+}
+
+/// Accepts the level shapes used by the venues we consume:
+/// `["price", "quantity"]`, the same with JSON numbers, `[price, quantity, timestamp, ..]` where the trailing
+/// elements are ignored, and `{"price": .., "size": ..}` (or `"quantity"`/`"qty"`).
+#[cfg(feature = "serde")]
+impl<'de, P, Q> Deserialize<'de> for PriceAndQuantity<P, Q>
+where
+    P: FromStr,
+    P::Err: Display,
+    Q: FromStr,
+    Q::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(LevelVisitor(PhantomData))
+    }
+}
+
+struct LevelVisitor<P, Q>(PhantomData<(P, Q)>);
+
+impl<'de, P, Q> Visitor<'de> for LevelVisitor<P, Q>
+where
+    P: FromStr,
+    P::Err: Display,
+    Q: FromStr,
+    Q::Err: Display,
+{
+    type Value = PriceAndQuantity<P, Q>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a [price, quantity] sequence or a map with price and size")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let price = seq
+            .next_element_seed(FromStrSeed(PhantomData))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let quantity = seq
+            .next_element_seed(FromStrSeed(PhantomData))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(PriceAndQuantity(price, quantity))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut price = None;
+        let mut quantity = None;
+        while let Some(key) = map.next_key::<LevelField>()? {
+            match key {
+                LevelField::Price => price = Some(map.next_value_seed(FromStrSeed(PhantomData))?),
+                LevelField::Quantity => {
+                    quantity = Some(map.next_value_seed(FromStrSeed(PhantomData))?)
+                }
+                LevelField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
         }
+        Ok(PriceAndQuantity(
+            price.ok_or_else(|| de::Error::missing_field("price"))?,
+            quantity.ok_or_else(|| de::Error::missing_field("size"))?,
+        ))
     }
+}
 
-    deserializer.deserialize_str(NumberFromStr::<T>(PhantomData))
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum LevelField {
+    Price,
+    #[serde(alias = "size", alias = "qty")]
+    Quantity,
+    #[serde(other)]
+    Other,
 }
 
 ///Not commutative. It adds quantity while copying the rhs' price. Also, not associative if prices differ.
@@ -110,6 +261,42 @@ impl<P, Q: Add<Output = Q>> Add for PriceAndQuantity<P, Q> {
 pub mod tests {
     use super::*;
 
+    #[test]
+    fn deserialize_strings_and_numbers() {
+        let expected = PriceAndQuantity(27826.89, 2.5);
+        for level in [
+            r#"["27826.89", "2.5"]"#,
+            r#"[27826.89, 2.5]"#,
+            r#"["27826.89", 2.5]"#,
+            r#"[27826.89, "2.5", 1687440000123]"#,
+            r#"{"price": 27826.89, "size": "2.5"}"#,
+            r#"{"price": "27826.89", "quantity": 2.5, "side": "buy"}"#,
+        ] {
+            let parsed: PriceAndQuantity<f64, f64> = serde_json::from_str(level).unwrap();
+            assert_eq!(parsed, expected, "{level}");
+        }
+    }
+
+    #[test]
+    fn deserialize_integers() {
+        let parsed: PriceAndQuantity<f64, u32> = serde_json::from_str("[27826, 3]").unwrap();
+        assert_eq!(parsed, PriceAndQuantity(27826., 3));
+    }
+
+    #[test]
+    fn deserialize_rejects_short_levels() {
+        assert!(serde_json::from_str::<PriceAndQuantity<f64, f64>>(r#"["1.0"]"#).is_err());
+        assert!(serde_json::from_str::<PriceAndQuantity<f64, f64>>(r#"{"price": 1}"#).is_err());
+    }
+
+    #[test]
+    fn serialize_as_strings() {
+        let level = serde_json::to_string(&PriceAndQuantity(27826.89, 2.5)).unwrap();
+        assert_eq!(level, r#"["27826.89","2.5"]"#);
+        let parsed: PriceAndQuantity<f64, f64> = serde_json::from_str(&level).unwrap();
+        assert_eq!(parsed, PriceAndQuantity(27826.89, 2.5));
+    }
+
     #[test]
     fn add_quantity_only() {
         assert_eq!(
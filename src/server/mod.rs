@@ -0,0 +1,575 @@
+//! gRPC services over the books of a [BookStore].
+
+use crate::limit_order_book::protos::{
+    self, book_event::Event, limit_order_book_service_server::LimitOrderBookService, BookEvent,
+    BookRequest, BookSide, LevelEncoding, Pair,
+};
+use crate::limit_order_book::SyncState;
+use crate::LimitOrderBook;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::service::Routes;
+use tonic::{Request, Response, Status};
+use tonic_health::server::HealthReporter;
+
+pub use orders::{OrderEntryServiceServer, OrderService};
+pub use protos::limit_order_book_service_server::LimitOrderBookServiceServer;
+pub use store::{BookStore, StoreEvent};
+
+mod orders;
+mod store;
+
+/// Events queued per subscriber before it stops pulling from the store and starts lagging.
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// Serves a [BookStore], the pair is the symbol the book was added under.
+#[derive(Clone, Debug, Default)]
+pub struct BookService {
+    store: Arc<BookStore>,
+}
+
+impl BookService {
+    pub fn new(store: Arc<BookStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn into_server(self) -> LimitOrderBookServiceServer<Self> {
+        LimitOrderBookServiceServer::new(self)
+    }
+}
+
+/// The [BookService] of `store` alongside gRPC server reflection (v1 and v1alpha) and the standard health service,
+/// so that clients can introspect and probe the server without the .proto.
+/// The book service is reported serving, the returned reporter lets the caller change that.
+pub async fn routes(
+    store: Arc<BookStore>,
+) -> Result<(Routes, HealthReporter), tonic_reflection::server::Error> {
+    let reflection = || {
+        tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(protos::FILE_DESCRIPTOR_SET)
+            .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+    };
+    let (mut reporter, health) = tonic_health::server::health_reporter();
+    reporter
+        .set_serving::<LimitOrderBookServiceServer<BookService>>()
+        .await;
+    let routes = Routes::new(BookService::new(store).into_server())
+        .add_service(health)
+        .add_service(reflection().build_v1()?)
+        .add_service(reflection().build_v1alpha()?);
+    Ok((routes, reporter))
+}
+
+fn not_found(pair: &str) -> Status {
+    Status::not_found(format!("no book for pair {pair}"))
+}
+
+enum Snapshot<T> {
+    Book(T),
+    Awaiting,
+    NotFound,
+}
+
+/// `view` of the book of `pair`, taken under the read lock.
+fn snapshot<T>(
+    store: &BookStore,
+    pair: &str,
+    view: impl FnOnce(&LimitOrderBook) -> T,
+) -> Snapshot<T> {
+    let books = store.read();
+    match (books.state(pair), books.book(pair)) {
+        (Some(SyncState::Synced), Some(book)) => Snapshot::Book(view(book)),
+        (Some(_), _) => Snapshot::Awaiting,
+        (None, _) => Snapshot::NotFound,
+    }
+}
+
+fn unknown_encoding(encoding: i32) -> Status {
+    Status::invalid_argument(format!("unknown level encoding {encoding}"))
+}
+
+/// The book grouped, then truncated to `depth` levels, with only the requested side.
+fn book_view(
+    book: &LimitOrderBook,
+    depth: Option<u32>,
+    group: Option<f64>,
+    side: BookSide,
+    encoding: LevelEncoding,
+) -> protos::LimitOrderBook {
+    let top_of_book = book.top_of_book();
+    let mut view = match group {
+        Some(tick) => book.grouped(tick),
+        None => book.clone(),
+    };
+    if let Some(depth) = depth {
+        view.truncate(depth as usize);
+    }
+    let mut view = protos::LimitOrderBook::encode(view, encoding);
+    match side {
+        BookSide::Both => {}
+        BookSide::Bids => view.asks = Some(Default::default()),
+        BookSide::Asks => view.bids = Some(Default::default()),
+    }
+    view.top_of_book = Some(top_of_book.into());
+    view
+}
+
+/// Sends the book of `pair` then the updates applied to it. Updates already in the last snapshot sent are skipped.
+/// A book replaced in the store is always sent. A subscriber that lags behind the store's capacity is sent a new
+/// snapshot instead of the missed updates, unless the book is still at the last one sent.
+async fn forward(
+    store: Arc<BookStore>,
+    pair: String,
+    encoding: LevelEncoding,
+    mut events: broadcast::Receiver<StoreEvent>,
+    subscriber: mpsc::Sender<Result<BookEvent, Status>>,
+) {
+    let mut last_sent = None;
+    let mut resnapshot = true;
+    let mut lagged = false;
+    loop {
+        let event = if resnapshot {
+            resnapshot = false;
+            // After a lag, the subscriber already has the book at that update id. A replaced book may have the
+            // same id with other levels, e.g. when ids are assigned by the caller.
+            let unchanged = if std::mem::take(&mut lagged) {
+                last_sent
+            } else {
+                None
+            };
+            match snapshot(&store, &pair, |book| {
+                (unchanged != Some(book.update_id))
+                    .then(|| protos::LimitOrderBook::encode(book.clone(), encoding))
+            }) {
+                Snapshot::Book(None) => continue,
+                Snapshot::Book(Some(book)) => {
+                    last_sent = Some(book.update_id);
+                    Ok(Event::Snapshot(book))
+                }
+                Snapshot::Awaiting => {
+                    last_sent = None;
+                    continue;
+                }
+                Snapshot::NotFound => Err(not_found(&pair)),
+            }
+        } else {
+            match events.recv().await {
+                Ok(StoreEvent::Applied { symbol, update }) if *symbol == *pair => match last_sent {
+                    Some(last) if update.last_update_id > last => {
+                        last_sent = Some(update.last_update_id);
+                        Ok(Event::Update(protos::DepthUpdate::encode(
+                            &update, encoding,
+                        )))
+                    }
+                    _ => continue,
+                },
+                Ok(StoreEvent::Snapshot { symbol }) if *symbol == *pair => {
+                    resnapshot = true;
+                    continue;
+                }
+                Ok(StoreEvent::Removed { symbol }) if *symbol == *pair => Err(not_found(&pair)),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    resnapshot = true;
+                    lagged = true;
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        };
+
+        let done = event.is_err();
+        let event = event.map(|event| BookEvent { event: Some(event) });
+        if subscriber.send(event).await.is_err() || done {
+            return;
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl LimitOrderBookService for BookService {
+    /// `NOT_FOUND` for a pair that isn't tracked, `UNAVAILABLE` while its book awaits a snapshot.
+    /// `INVALID_ARGUMENT` for a grouping that isn't a positive price, an unknown side or encoding.
+    async fn get_limit_order_book(
+        &self,
+        request: Request<BookRequest>,
+    ) -> Result<Response<protos::LimitOrderBook>, Status> {
+        let request = request.into_inner();
+        let side = BookSide::try_from(request.side)
+            .map_err(|_| Status::invalid_argument(format!("unknown side {}", request.side)))?;
+        let encoding = LevelEncoding::try_from(request.encoding)
+            .map_err(|_| unknown_encoding(request.encoding))?;
+        if let Some(group) = request
+            .group
+            .filter(|group| !(group.is_finite() && *group > 0.))
+        {
+            return Err(Status::invalid_argument(format!(
+                "group must be a positive price, got {group}"
+            )));
+        }
+        let pair = request.pair;
+        match snapshot(&self.store, &pair, |book| {
+            book_view(book, request.depth, request.group, side, encoding)
+        }) {
+            Snapshot::Book(book) => Ok(Response::new(book)),
+            Snapshot::Awaiting => Err(Status::unavailable(format!(
+                "book for pair {pair} awaits a snapshot"
+            ))),
+            Snapshot::NotFound => Err(not_found(&pair)),
+        }
+    }
+
+    type SubscribeLimitOrderBookStream = ReceiverStream<Result<BookEvent, Status>>;
+
+    /// `NOT_FOUND` for a pair that isn't tracked. A book awaiting a snapshot starts streaming once it has one.
+    async fn subscribe_limit_order_book(
+        &self,
+        request: Request<Pair>,
+    ) -> Result<Response<Self::SubscribeLimitOrderBookStream>, Status> {
+        let Pair { pair, encoding } = request.into_inner();
+        let encoding = LevelEncoding::try_from(encoding).map_err(|_| unknown_encoding(encoding))?;
+        // Subscribe before the first snapshot is taken so that no update falls in between.
+        let events = self.store.subscribe();
+        if self.store.read().state(&pair).is_none() {
+            return Err(not_found(&pair));
+        }
+        let (subscriber, stream) = mpsc::channel(SUBSCRIBER_BUFFER);
+        tokio::spawn(forward(
+            self.store.clone(),
+            pair,
+            encoding,
+            events,
+            subscriber,
+        ));
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::limit_order_book::sequence::Market;
+    use crate::{DepthUpdate, LimitOrderBook, PriceAndQuantity};
+    use protos::limit_order_book_service_client::LimitOrderBookServiceClient;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+    use tonic::Code;
+
+    /// Serves `routes` on a loopback port, returns a channel connected to it.
+    pub(crate) async fn channel_with(routes: Routes) -> Channel {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_routes(routes)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        Channel::from_shared(format!("http://{address}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    /// Serves the [routes] of `store`.
+    pub(crate) async fn channel(store: Arc<BookStore>) -> Channel {
+        channel_with(routes(store).await.unwrap().0).await
+    }
+
+    pub(crate) async fn serve(store: Arc<BookStore>) -> LimitOrderBookServiceClient<Channel> {
+        LimitOrderBookServiceClient::new(channel(store).await)
+    }
+
+    fn pair(pair: &str) -> Request<Pair> {
+        Request::new(Pair {
+            pair: pair.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    fn book(update_id: u64) -> LimitOrderBook {
+        LimitOrderBook::from_sides(
+            update_id,
+            vec![PriceAndQuantity(1., 2.)].into(),
+            vec![PriceAndQuantity(3., 4.)].into(),
+        )
+    }
+
+    fn update(update_id: u64, bid: f64) -> DepthUpdate {
+        DepthUpdate {
+            first_update_id: update_id,
+            last_update_id: update_id,
+            bids: vec![PriceAndQuantity(bid, 1.)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn book_request(pair: &str) -> BookRequest {
+        BookRequest {
+            pair: pair.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn get_limit_order_book() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        store.add("ETHUSDT", Market::Spot);
+        store.snapshot("BTCUSDT", book(7)).unwrap();
+        let mut client = serve(store.clone()).await;
+
+        let response = client
+            .get_limit_order_book(book_request("BTCUSDT"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            LimitOrderBook::from(response.clone()),
+            *store.read().book("BTCUSDT").unwrap()
+        );
+        let top_of_book = response.top_of_book.unwrap();
+        assert_eq!(top_of_book.spread, Some(2.));
+        assert_eq!(top_of_book.mid, Some(2.));
+
+        let status = client
+            .get_limit_order_book(book_request("ETHUSDT"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let status = client
+            .get_limit_order_book(book_request("DOGEUSDT"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    #[test]
+    fn book_request_reads_pairs() {
+        use prost::Message;
+        let pair = Pair {
+            pair: "BTCUSDT".to_owned(),
+            encoding: LevelEncoding::Scaled.into(),
+        };
+        let request = BookRequest::decode(&pair.encode_to_vec()[..]).unwrap();
+        assert_eq!(
+            request,
+            BookRequest {
+                encoding: LevelEncoding::Scaled.into(),
+                ..book_request("BTCUSDT")
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn get_limit_order_book_view() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        let bids = vec![
+            PriceAndQuantity(97., 1.),
+            PriceAndQuantity(98.5, 2.),
+            PriceAndQuantity(99.5, 3.),
+        ];
+        let asks = vec![PriceAndQuantity(101., 4.), PriceAndQuantity(100.5, 5.)];
+        let full = LimitOrderBook::from_sides(7, bids.into(), asks.into());
+        store.snapshot("BTCUSDT", full).unwrap();
+        let mut client = serve(store).await;
+
+        let response = client
+            .get_limit_order_book(BookRequest {
+                depth: Some(1),
+                group: Some(2.),
+                side: BookSide::Bids.into(),
+                ..book_request("BTCUSDT")
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let view = LimitOrderBook::from(response.clone());
+        assert_eq!(**view.bids(), [PriceAndQuantity(98., 5.)]);
+        assert!(view.asks().is_empty());
+        let top_of_book = response.top_of_book.unwrap();
+        assert_eq!(
+            top_of_book.best_bid,
+            Some(protos::PriceAndQuantity {
+                price: 99.5,
+                quantity: 3.
+            })
+        );
+        assert_eq!(top_of_book.spread, Some(1.));
+        assert_eq!(top_of_book.mid, Some(100.));
+
+        for request in [
+            BookRequest {
+                group: Some(0.),
+                ..book_request("BTCUSDT")
+            },
+            BookRequest {
+                side: 7,
+                ..book_request("BTCUSDT")
+            },
+            BookRequest {
+                encoding: 7,
+                ..book_request("BTCUSDT")
+            },
+        ] {
+            let status = client.get_limit_order_book(request).await.unwrap_err();
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn reflection_and_health() {
+        use tonic_health::pb::HealthCheckRequest;
+        use tonic_health::pb::{health_check_response::ServingStatus, health_client::HealthClient};
+        use tonic_reflection::pb::v1::{
+            server_reflection_client::ServerReflectionClient,
+            server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+            ServerReflectionRequest,
+        };
+
+        let channel = channel(BookStore::new()).await;
+
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = ServerReflectionClient::new(channel.clone())
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(MessageResponse::ListServicesResponse(list)) =
+            responses.message().await.unwrap().unwrap().message_response
+        else {
+            panic!("expected the list of services");
+        };
+        let services: Vec<_> = list
+            .service
+            .into_iter()
+            .map(|service| service.name)
+            .collect();
+        assert!(services.contains(&"protos.LimitOrderBookService".to_owned()));
+        assert!(services.contains(&"grpc.health.v1.Health".to_owned()));
+
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: "protos.LimitOrderBookService".to_owned(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status(), ServingStatus::Serving);
+    }
+
+    #[tokio::test]
+    async fn subscription_replicates_book() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        store.snapshot("BTCUSDT", book(7)).unwrap();
+        let mut client = serve(store.clone()).await;
+
+        let status = client
+            .subscribe_limit_order_book(pair("DOGEUSDT"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let mut stream = client
+            .subscribe_limit_order_book(pair("BTCUSDT"))
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(Event::Snapshot(snapshot)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a snapshot");
+        };
+        let mut replica = LimitOrderBook::from(snapshot);
+
+        for (update_id, bid) in [(8, 1.5), (9, 0.5)] {
+            store.apply_to("BTCUSDT", update(update_id, bid)).unwrap();
+        }
+        for _ in 0..2 {
+            let Some(Event::Update(update)) = stream.message().await.unwrap().unwrap().event else {
+                panic!("expected an update");
+            };
+            let update = DepthUpdate::from(update);
+            assert!(!update.skip_update(Market::Spot, replica.update_id));
+            replica.apply(&update);
+        }
+        assert_eq!(replica, *store.read().book("BTCUSDT").unwrap());
+
+        store.remove("BTCUSDT");
+        let status = stream.message().await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn lagging_subscriber_gets_snapshot() {
+        let store = Arc::new(BookStore::with_capacity(2));
+        store.add("BTCUSDT", Market::Spot);
+        store.snapshot("BTCUSDT", book(1)).unwrap();
+
+        let events = store.subscribe();
+        let (subscriber, mut stream) = mpsc::channel(1);
+        let forwarding = tokio::spawn(forward(
+            store.clone(),
+            "BTCUSDT".to_owned(),
+            LevelEncoding::Double,
+            events,
+            subscriber,
+        ));
+        // Overflows the broadcast capacity before the subscriber gets to run.
+        for update_id in 2..10 {
+            store.apply_to("BTCUSDT", update(update_id, 1.)).unwrap();
+        }
+
+        // The first snapshot already has the missed updates, the lag doesn't send it again.
+        let Some(Event::Snapshot(snapshot)) = stream.recv().await.unwrap().unwrap().event else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(snapshot.update_id, 9);
+        store.apply_to("BTCUSDT", update(10, 1.)).unwrap();
+        let Some(Event::Update(update)) = stream.recv().await.unwrap().unwrap().event else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.last_update_id, 10);
+
+        drop(stream);
+        store.apply_to("BTCUSDT", self::update(11, 1.)).unwrap();
+        forwarding.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn replaced_book_is_sent_at_the_same_id() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        store.snapshot("BTCUSDT", book(1)).unwrap();
+
+        let (subscriber, mut stream) = mpsc::channel(4);
+        tokio::spawn(forward(
+            store.clone(),
+            "BTCUSDT".to_owned(),
+            LevelEncoding::Double,
+            store.subscribe(),
+            subscriber,
+        ));
+        let Some(Event::Snapshot(snapshot)) = stream.recv().await.unwrap().unwrap().event else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(snapshot.update_id, 1);
+
+        let replaced = LimitOrderBook::from_sides(
+            1,
+            vec![PriceAndQuantity(5., 6.)].into(),
+            Default::default(),
+        );
+        store.snapshot("BTCUSDT", replaced.clone()).unwrap();
+        let Some(Event::Snapshot(snapshot)) = stream.recv().await.unwrap().unwrap().event else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(LimitOrderBook::from(snapshot), replaced);
+    }
+}
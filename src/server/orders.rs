@@ -0,0 +1,375 @@
+use super::{not_found, BookStore, SUBSCRIBER_BUFFER};
+use crate::limit_order_book::protos::{
+    self, order_command::Command, order_entry_service_server::OrderEntryService, AmendOrderRequest,
+    CancelOrderRequest, OrderCommand, OrderResponse, SubmitOrderRequest,
+};
+use crate::limit_order_book::sequence::Market;
+use crate::matching::{MatchingEngine, OrderError, Outcome};
+use crate::{Order, OrderType, PriceAndQuantity};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+pub use protos::order_entry_service_server::OrderEntryServiceServer;
+
+/// Why a command was refused.
+enum Rejection {
+    UnknownPair,
+    MissingCommand,
+    InvalidOrderType(i32),
+    Order(OrderError),
+}
+
+impl Rejection {
+    fn reason(&self, pair: &str) -> String {
+        match self {
+            Rejection::UnknownPair => format!("no book for pair {pair}"),
+            Rejection::MissingCommand => "missing command".to_owned(),
+            Rejection::InvalidOrderType(order_type) => format!("invalid order type {order_type}"),
+            Rejection::Order(error) => error.to_string(),
+        }
+    }
+
+    fn status(&self, pair: &str) -> Status {
+        match self {
+            Rejection::UnknownPair => not_found(pair),
+            Rejection::Order(OrderError::UnknownOrder(_)) => Status::not_found(self.reason(pair)),
+            _ => Status::invalid_argument(self.reason(pair)),
+        }
+    }
+}
+
+/// Matching engines of a mock exchange, one per pair. Their books are published to a [BookStore] as they change, so
+/// that a [super::BookService] of the same store serves them in real time.
+#[derive(Clone, Debug, Default)]
+pub struct OrderService {
+    store: Arc<BookStore>,
+    engines: Arc<Mutex<HashMap<String, MatchingEngine>>>,
+}
+
+impl OrderService {
+    pub fn new(store: Arc<BookStore>) -> Self {
+        Self {
+            store,
+            engines: Default::default(),
+        }
+    }
+
+    pub fn into_server(self) -> OrderEntryServiceServer<Self> {
+        OrderEntryServiceServer::new(self)
+    }
+
+    /// Lists `pair` with an empty book, false if the store already tracks it.
+    pub fn add(&self, pair: impl Into<String>) -> bool {
+        let pair = pair.into();
+        let mut engines = self.engines();
+        if !self.store.add(pair.clone(), Market::Spot) {
+            return false;
+        }
+        let engine = MatchingEngine::new();
+        self.store
+            .snapshot(&pair, engine.book())
+            .expect("the pair was just added");
+        engines.insert(pair, engine);
+        true
+    }
+
+    fn engines(&self) -> MutexGuard<'_, HashMap<String, MatchingEngine>> {
+        self.engines.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `command` on the engine of `pair` and publishes the update it made. Both happen under the engines' lock,
+    /// so the store gets the updates in the order they were made.
+    fn execute(
+        &self,
+        pair: &str,
+        command: impl FnOnce(&mut MatchingEngine) -> Result<Outcome, OrderError>,
+    ) -> Result<Vec<protos::ExecutionReport>, Rejection> {
+        let mut engines = self.engines();
+        let engine = engines.get_mut(pair).ok_or(Rejection::UnknownPair)?;
+        let Outcome { reports, update } = command(engine).map_err(Rejection::Order)?;
+        if let Some(update) = update {
+            // The pair may have been removed from the store, trading goes on without market data.
+            let _ = self.store.apply_to(pair, update);
+        }
+        let update_id = engine.update_id();
+        Ok(reports
+            .into_iter()
+            .map(|report| protos::ExecutionReport {
+                pair: pair.to_owned(),
+                order_id: report.order_id,
+                order_type: protos::OrderType::from(report.order_type).into(),
+                execution_type: protos::ExecutionType::from(report.execution_type).into(),
+                price: report.price,
+                quantity: report.quantity,
+                leaves_quantity: report.leaves_quantity,
+                update_id,
+                reason: String::new(),
+            })
+            .collect())
+    }
+
+    fn submit(
+        &self,
+        request: SubmitOrderRequest,
+    ) -> Result<Vec<protos::ExecutionReport>, Rejection> {
+        let order_type = OrderType::try_from(request.order_type)
+            .map_err(|_| Rejection::InvalidOrderType(request.order_type))?;
+        let order = Order::new(
+            PriceAndQuantity(request.price, request.quantity),
+            order_type,
+        );
+        self.execute(&request.pair, |engine| engine.submit(&order))
+    }
+
+    fn cancel(
+        &self,
+        request: CancelOrderRequest,
+    ) -> Result<Vec<protos::ExecutionReport>, Rejection> {
+        self.execute(&request.pair, |engine| engine.cancel(request.order_id))
+    }
+
+    fn amend(&self, request: AmendOrderRequest) -> Result<Vec<protos::ExecutionReport>, Rejection> {
+        self.execute(&request.pair, |engine| {
+            engine.amend(request.order_id, request.price, request.quantity)
+        })
+    }
+
+    /// Reports of a streamed command, a refused one gets a single rejection report.
+    fn command(&self, command: Option<Command>) -> Vec<protos::ExecutionReport> {
+        let (pair, order_id) = match &command {
+            Some(Command::Submit(request)) => (request.pair.clone(), 0),
+            Some(Command::Cancel(request)) => (request.pair.clone(), request.order_id),
+            Some(Command::Amend(request)) => (request.pair.clone(), request.order_id),
+            None => Default::default(),
+        };
+        let reports = match command {
+            Some(Command::Submit(request)) => self.submit(request),
+            Some(Command::Cancel(request)) => self.cancel(request),
+            Some(Command::Amend(request)) => self.amend(request),
+            None => Err(Rejection::MissingCommand),
+        };
+        reports.unwrap_or_else(|rejection| {
+            vec![protos::ExecutionReport {
+                reason: rejection.reason(&pair),
+                pair,
+                order_id,
+                execution_type: protos::ExecutionType::Rejected.into(),
+                ..Default::default()
+            }]
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl OrderEntryService for OrderService {
+    async fn submit_order(
+        &self,
+        request: Request<SubmitOrderRequest>,
+    ) -> Result<Response<OrderResponse>, Status> {
+        let request = request.into_inner();
+        let pair = request.pair.clone();
+        self.submit(request)
+            .map(|reports| Response::new(OrderResponse { reports }))
+            .map_err(|rejection| rejection.status(&pair))
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<CancelOrderRequest>,
+    ) -> Result<Response<OrderResponse>, Status> {
+        let request = request.into_inner();
+        let pair = request.pair.clone();
+        self.cancel(request)
+            .map(|reports| Response::new(OrderResponse { reports }))
+            .map_err(|rejection| rejection.status(&pair))
+    }
+
+    async fn amend_order(
+        &self,
+        request: Request<AmendOrderRequest>,
+    ) -> Result<Response<OrderResponse>, Status> {
+        let request = request.into_inner();
+        let pair = request.pair.clone();
+        self.amend(request)
+            .map(|reports| Response::new(OrderResponse { reports }))
+            .map_err(|rejection| rejection.status(&pair))
+    }
+
+    type ExecutionReportsStream = ReceiverStream<Result<protos::ExecutionReport, Status>>;
+
+    /// The stream ends with the client's, or on its first error.
+    async fn execution_reports(
+        &self,
+        request: Request<Streaming<OrderCommand>>,
+    ) -> Result<Response<Self::ExecutionReportsStream>, Status> {
+        let mut commands = request.into_inner();
+        let (reports, stream) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let service = self.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(OrderCommand { command })) = commands.message().await {
+                for report in service.command(command) {
+                    if reports.send(Ok(report)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::channel_with;
+    use super::super::{routes, BookStore};
+    use super::*;
+    use crate::LimitOrderBook;
+    use protos::book_event::Event;
+    use protos::limit_order_book_service_client::LimitOrderBookServiceClient;
+    use protos::order_entry_service_client::OrderEntryServiceClient;
+    use protos::{ExecutionType, Pair};
+    use tonic::Code;
+
+    fn submit(order_type: protos::OrderType, price: f64, quantity: f64) -> SubmitOrderRequest {
+        SubmitOrderRequest {
+            pair: "BTCUSDT".to_owned(),
+            order_type: order_type.into(),
+            price,
+            quantity,
+        }
+    }
+
+    #[tokio::test]
+    async fn orders_move_the_served_book() {
+        let store = BookStore::new();
+        let orders = OrderService::new(store.clone());
+        assert!(orders.add("BTCUSDT"));
+        assert!(!orders.add("BTCUSDT"));
+        let (routes, _) = routes(store.clone()).await.unwrap();
+        let channel = channel_with(routes.add_service(orders.into_server())).await;
+        let mut client = OrderEntryServiceClient::new(channel.clone());
+        let mut market_data = LimitOrderBookServiceClient::new(channel);
+
+        let mut book_events = market_data
+            .subscribe_limit_order_book(Pair {
+                pair: "BTCUSDT".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(Event::Snapshot(snapshot)) = book_events.message().await.unwrap().unwrap().event
+        else {
+            panic!("expected a snapshot");
+        };
+        let mut replica = LimitOrderBook::from(snapshot);
+
+        let reports = client
+            .submit_order(submit(protos::OrderType::Sell, 100., 2.))
+            .await
+            .unwrap()
+            .into_inner()
+            .reports;
+        assert_eq!(reports[0].execution_type(), ExecutionType::New);
+        let maker = reports[0].order_id;
+        let reports = client
+            .submit_order(submit(protos::OrderType::Buy, 100., 0.5))
+            .await
+            .unwrap()
+            .into_inner()
+            .reports;
+        let trades: Vec<_> = reports
+            .iter()
+            .filter(|report| report.execution_type() == ExecutionType::Trade)
+            .map(|report| (report.order_id, report.quantity, report.leaves_quantity))
+            .collect();
+        assert_eq!(trades, [(reports[0].order_id, 0.5, 0.), (maker, 0.5, 1.5)]);
+
+        for _ in 0..2 {
+            let Some(Event::Update(update)) = book_events.message().await.unwrap().unwrap().event
+            else {
+                panic!("expected an update");
+            };
+            replica.apply(&update.into());
+        }
+        assert_eq!(replica, *store.read().book("BTCUSDT").unwrap());
+        assert_eq!(**replica.asks(), [PriceAndQuantity(100., 1.5)]);
+
+        let status = client
+            .cancel_order(CancelOrderRequest {
+                pair: "BTCUSDT".to_owned(),
+                order_id: 42,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        let status = client
+            .submit_order(submit(protos::OrderType::Buy, -1., 1.))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let status = client
+            .submit_order(submit(protos::OrderType::Unspecified, 100., 1.))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn execution_report_stream() {
+        let store = BookStore::new();
+        let orders = OrderService::new(store.clone());
+        orders.add("BTCUSDT");
+        let mut client = OrderEntryServiceClient::new(
+            channel_with(tonic::service::Routes::new(orders.into_server())).await,
+        );
+
+        let commands = [
+            Command::Submit(submit(protos::OrderType::Buy, 99., 1.)),
+            Command::Amend(AmendOrderRequest {
+                pair: "BTCUSDT".to_owned(),
+                order_id: 1,
+                price: 99.5,
+                quantity: 1.,
+            }),
+            Command::Cancel(CancelOrderRequest {
+                pair: "ETHUSDT".to_owned(),
+                order_id: 1,
+            }),
+            Command::Cancel(CancelOrderRequest {
+                pair: "BTCUSDT".to_owned(),
+                order_id: 1,
+            }),
+        ]
+        .map(|command| OrderCommand {
+            command: Some(command),
+        });
+        let mut reports = client
+            .execution_reports(tokio_stream::iter(commands))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut received = Vec::new();
+        while let Some(report) = reports.message().await.unwrap() {
+            received.push((report.execution_type(), report.update_id, report.reason));
+        }
+        assert_eq!(
+            received,
+            [
+                (ExecutionType::New, 1, String::new()),
+                (ExecutionType::Amended, 2, String::new()),
+                (
+                    ExecutionType::Rejected,
+                    0,
+                    "no book for pair ETHUSDT".to_owned()
+                ),
+                (ExecutionType::Cancelled, 3, String::new()),
+            ]
+        );
+        assert!(store.read().book("BTCUSDT").unwrap().bids().is_empty());
+    }
+}
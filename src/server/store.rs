@@ -0,0 +1,150 @@
+use crate::limit_order_book::sequence::Market;
+use crate::limit_order_book::{BookManager, Routed, SyncState, UnknownSymbol};
+use crate::{DepthUpdate, LimitOrderBook};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::broadcast;
+
+/// Updates kept for lagging subscribers, past that they are sent a new snapshot.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Change to a book of a [BookStore], as seen by subscribers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoreEvent {
+    /// The update was applied on top of the book.
+    Applied {
+        symbol: Arc<str>,
+        update: Arc<DepthUpdate>,
+    },
+    /// The book was replaced by a snapshot, replicas must be rebuilt. The book may await another snapshot if the
+    /// buffered updates didn't connect to this one.
+    Snapshot {
+        symbol: Arc<str>,
+    },
+    Removed {
+        symbol: Arc<str>,
+    },
+}
+
+/// [BookManager] shared between the feed handlers writing it and the gRPC services reading it.
+/// Every change is broadcast so that subscribers can replicate the books. Events are published while the
+/// write lock is held, so they come in the order the changes were made.
+#[derive(Debug)]
+pub struct BookStore {
+    books: RwLock<BookManager>,
+    events: broadcast::Sender<StoreEvent>,
+}
+
+impl Default for BookStore {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl BookStore {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// `capacity` events are kept for subscribers that fall behind.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            books: RwLock::new(BookManager::new()),
+            events: broadcast::channel(capacity).0,
+        }
+    }
+
+    /// The books, a poisoned lock is recovered as every write leaves the manager consistent.
+    pub fn read(&self) -> RwLockReadGuard<'_, BookManager> {
+        self.books.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BookManager> {
+        self.books.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StoreEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn add(&self, symbol: impl Into<String>, market: Market) -> bool {
+        self.write().add(symbol, market)
+    }
+
+    pub fn remove(&self, symbol: &str) -> Option<LimitOrderBook> {
+        let mut books = self.write();
+        let book = books.remove(symbol)?;
+        self.publish(StoreEvent::Removed {
+            symbol: symbol.into(),
+        });
+        Some(book)
+    }
+
+    /// See [BookManager::snapshot]. Published whatever the resulting state, the book was replaced either way.
+    pub fn snapshot(&self, symbol: &str, book: LimitOrderBook) -> Result<SyncState, UnknownSymbol> {
+        let mut books = self.write();
+        let state = books.snapshot(symbol, book)?;
+        self.publish(StoreEvent::Snapshot {
+            symbol: symbol.into(),
+        });
+        Ok(state)
+    }
+
+    /// See [BookManager::apply_to], only applied updates are published.
+    pub fn apply_to(&self, symbol: &str, update: DepthUpdate) -> Result<Routed, UnknownSymbol> {
+        let update = Arc::new(update);
+        let mut books = self.write();
+        let routed = books.apply_to(symbol, DepthUpdate::clone(&update))?;
+        if routed == Routed::Applied {
+            self.publish(StoreEvent::Applied {
+                symbol: symbol.into(),
+                update,
+            });
+        }
+        Ok(routed)
+    }
+
+    /// See [BookManager::apply].
+    #[cfg(feature = "event-symbol")]
+    pub fn apply(&self, update: DepthUpdate) -> Result<Routed, UnknownSymbol> {
+        let symbol = update.event.symbol.clone();
+        self.apply_to(&symbol, update)
+    }
+
+    fn publish(&self, event: StoreEvent) {
+        // No subscriber is not an error.
+        let _ = self.events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PriceAndQuantity;
+
+    #[test]
+    fn replaced_books_are_published() {
+        let store = BookStore::new();
+        store.add("BTCUSDT", Market::Spot);
+        let mut events = store.subscribe();
+
+        // The buffered update doesn't connect, the book is replaced but still awaits a snapshot.
+        let update = DepthUpdate {
+            first_update_id: 5,
+            last_update_id: 5,
+            bids: vec![PriceAndQuantity(1., 1.)].into(),
+            ..Default::default()
+        };
+        assert_eq!(store.apply_to("BTCUSDT", update), Ok(Routed::Buffered));
+        let book = LimitOrderBook::from_sides(1, Default::default(), Default::default());
+        assert_eq!(
+            store.snapshot("BTCUSDT", book),
+            Ok(SyncState::AwaitingSnapshot)
+        );
+        assert_eq!(
+            events.try_recv(),
+            Ok(StoreEvent::Snapshot {
+                symbol: "BTCUSDT".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use crate::ops::{
+    update_strategies::{AggregateOrCreate, ReplaceOrRemove},
+    PartitionPredicate, Update,
+};
+use crate::{Asks, Bids, PriceAndQuantity};
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul};
+
+/// Independent accumulators of the bulk queries. A single running sum is one chain of dependent additions the compiler
+/// can't reorder for floats, one sum per lane lets it keep them in vector registers.
+const LANES: usize = 8;
+
+fn sum_lanes<N: Add<Output = N> + Copy>(lanes: [N; LANES]) -> N {
+    // Pairwise, in the same shape as a horizontal vector sum.
+    let [a, b, c, d, e, f, g, h] = lanes;
+    ((a + b) + (c + d)) + ((e + f) + (g + h))
+}
+
+/// Structure of arrays layout for one side of the book.
+/// Prices and quantities live in separate vectors so bulk scans over thousands of levels touch contiguous memory.
+/// `S` is only used for its [PartitionPredicate], i.e. [Bids] or [Asks]; levels keep the same order as in those types, best level last.
+#[derive(Clone, Debug, Default)]
+pub struct SoaLevels<S, P = f64, Q = f64> {
+    prices: Vec<P>,
+    quantities: Vec<Q>,
+    // Copy of the level found by the last `entry` call, [Update::entry] must hand out a reference to a level.
+    probe: Option<PriceAndQuantity<P, Q>>,
+    side: PhantomData<S>,
+}
+
+pub type SoaBids<P = f64, Q = f64> = SoaLevels<Bids, P, Q>;
+pub type SoaAsks<P = f64, Q = f64> = SoaLevels<Asks, P, Q>;
+
+impl<S, P: PartialEq, Q: PartialEq> PartialEq for SoaLevels<S, P, Q> {
+    fn eq(&self, other: &Self) -> bool {
+        self.prices == other.prices && self.quantities == other.quantities
+    }
+}
+
+impl<S, P, Q> SoaLevels<S, P, Q> {
+    pub fn new() -> Self {
+        Self {
+            prices: Vec::new(),
+            quantities: Vec::new(),
+            probe: None,
+            side: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    pub fn prices(&self) -> &[P] {
+        &self.prices
+    }
+
+    pub fn quantities(&self) -> &[Q] {
+        &self.quantities
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = PriceAndQuantity<P, Q>> + '_
+    where
+        P: Copy,
+        Q: Copy,
+    {
+        self.prices
+            .iter()
+            .zip(self.quantities.iter())
+            .map(|(p, q)| PriceAndQuantity(*p, *q))
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.prices.remove(index);
+        self.quantities.remove(index);
+    }
+
+    fn level(&self, index: usize) -> Option<PriceAndQuantity<P, Q>>
+    where
+        P: Copy,
+        Q: Copy,
+    {
+        Some(PriceAndQuantity(
+            *self.prices.get(index)?,
+            self.quantities[index],
+        ))
+    }
+
+    fn insert(&mut self, index: usize, level: PriceAndQuantity<P, Q>) {
+        self.prices.insert(index, level.0);
+        self.quantities.insert(index, level.1);
+    }
+}
+
+impl<S: PartitionPredicate, P: PartialOrd, Q> SoaLevels<S, P, Q> {
+    /// Index of the first level at `price` or better, every level from there to the end is at least as good as `price`.
+    fn cutoff(&self, price: &P) -> usize {
+        self.prices
+            .partition_point(|value| S::partition_predicate(value, price))
+    }
+
+    /// Total quantity resting at `price` or better.
+    pub fn cumulative_quantity(&self, price: &P) -> Q
+    where
+        Q: Add<Output = Q> + Default + Copy,
+    {
+        let quantities = &self.quantities[self.cutoff(price)..];
+        let mut lanes = [Q::default(); LANES];
+        let chunks = quantities.chunks_exact(LANES);
+        let remainder = chunks.remainder();
+        for chunk in chunks {
+            for (lane, q) in lanes.iter_mut().zip(chunk) {
+                *lane = *lane + *q;
+            }
+        }
+        for (lane, q) in lanes.iter_mut().zip(remainder) {
+            *lane = *lane + *q;
+        }
+        sum_lanes(lanes)
+    }
+
+    /// Total notional (price times quantity) resting at `price` or better.
+    pub fn cumulative_notional<N>(&self, price: &P) -> N
+    where
+        P: Mul<Q, Output = N> + Copy,
+        Q: Copy,
+        N: Add<Output = N> + Default + Copy,
+    {
+        let from = self.cutoff(price);
+        let prices = self.prices[from..].chunks_exact(LANES);
+        let quantities = self.quantities[from..].chunks_exact(LANES);
+        let remainder = prices.remainder().iter().zip(quantities.remainder());
+        let mut lanes = [N::default(); LANES];
+        for (prices, quantities) in prices.zip(quantities) {
+            for (lane, (p, q)) in lanes.iter_mut().zip(prices.iter().zip(quantities)) {
+                *lane = *lane + *p * *q;
+            }
+        }
+        for (lane, (p, q)) in lanes.iter_mut().zip(remainder) {
+            *lane = *lane + *p * *q;
+        }
+        sum_lanes(lanes)
+    }
+}
+
+impl<S> SoaLevels<S, f64, f64>
+where
+    S: PartitionPredicate,
+{
+    /// Volume weighted average price of the levels at `price` or better, [None] if there is no quantity.
+    pub fn vwap(&self, price: &f64) -> Option<f64> {
+        let quantity = self.cumulative_quantity(price);
+        (quantity != 0.).then(|| self.cumulative_notional::<f64>(price) / quantity)
+    }
+}
+
+impl<S: PartitionPredicate, P, Q> PartitionPredicate for SoaLevels<S, P, Q> {
+    fn partition_predicate<Price: PartialOrd>(lhs: &Price, rhs: &Price) -> bool {
+        S::partition_predicate(lhs, rhs)
+    }
+}
+
+impl<S, P, Q> FromIterator<PriceAndQuantity<P, Q>> for SoaLevels<S, P, Q> {
+    /// Does not sort, the iterator must already yield levels in the side's order.
+    fn from_iter<T: IntoIterator<Item = PriceAndQuantity<P, Q>>>(iter: T) -> Self {
+        let (prices, quantities) = iter.into_iter().map(|level| (level.0, level.1)).unzip();
+        Self {
+            prices,
+            quantities,
+            probe: None,
+            side: PhantomData,
+        }
+    }
+}
+
+impl<P: Copy, Q: Copy> From<&Bids<P, Q>> for SoaBids<P, Q> {
+    fn from(bids: &Bids<P, Q>) -> Self {
+        bids.iter().copied().collect()
+    }
+}
+
+impl<P: Copy, Q: Copy> From<&Asks<P, Q>> for SoaAsks<P, Q> {
+    fn from(asks: &Asks<P, Q>) -> Self {
+        asks.iter().copied().collect()
+    }
+}
+
+impl<P: Copy, Q: Copy> From<&SoaBids<P, Q>> for Bids<P, Q> {
+    fn from(bids: &SoaBids<P, Q>) -> Self {
+        bids.iter().collect::<Vec<_>>().into()
+    }
+}
+
+impl<P: Copy, Q: Copy> From<&SoaAsks<P, Q>> for Asks<P, Q> {
+    fn from(asks: &SoaAsks<P, Q>) -> Self {
+        asks.iter().collect::<Vec<_>>().into()
+    }
+}
+
+impl<S, P, Q> Display for SoaLevels<S, P, Q>
+where
+    P: Display,
+    Q: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, (p, q)) in self.prices.iter().zip(self.quantities.iter()).enumerate() {
+            write!(f, "{}:{}", p, q)?;
+            if i < self.prices.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+
+        write!(f, "]")?;
+        Ok(())
+    }
+}
+
+impl<S, P, Q> Update<ReplaceOrRemove> for SoaLevels<S, P, Q>
+where
+    S: PartitionPredicate,
+    P: Copy,
+    Q: Add<Q, Output = Q> + Copy,
+{
+    type Level = PriceAndQuantity<P, Q>;
+    type Key = usize;
+
+    fn entry(&mut self, rhs: &Self::Level) -> (Self::Key, Option<&Self::Level>)
+    where
+        P: PartialOrd,
+    {
+        let index = self.cutoff(&rhs.0);
+        self.probe = self.level(index);
+        (index, self.probe.as_ref())
+    }
+
+    fn digest_operation(
+        &mut self,
+        operator: ReplaceOrRemove,
+        key: &usize,
+        level_update: Self::Level,
+    ) {
+        match operator {
+            ReplaceOrRemove::Replace => {
+                self.prices[*key] = level_update.0;
+                self.quantities[*key] = level_update.1;
+            }
+            ReplaceOrRemove::Remove => {
+                self.remove(*key);
+            }
+            ReplaceOrRemove::Displace => {
+                self.insert(*key, level_update);
+            }
+            ReplaceOrRemove::Noop => {}
+        }
+    }
+}
+
+impl<S, P, Q> Update<AggregateOrCreate> for SoaLevels<S, P, Q>
+where
+    S: PartitionPredicate,
+    P: Copy,
+    Q: Add<Q, Output = Q> + Copy,
+{
+    type Level = PriceAndQuantity<P, Q>;
+    type Key = usize;
+
+    fn entry(&mut self, rhs: &Self::Level) -> (Self::Key, Option<&Self::Level>)
+    where
+        P: PartialOrd,
+    {
+        let index = self.cutoff(&rhs.0);
+        self.probe = self.level(index);
+        (index, self.probe.as_ref())
+    }
+
+    fn digest_operation(&mut self, operator: AggregateOrCreate, key: &usize, new: Self::Level) {
+        match operator {
+            AggregateOrCreate::Aggregated => {
+                let q = self.quantities[*key] + new.1;
+                self.quantities[*key] = q;
+            }
+            AggregateOrCreate::Remove => {
+                self.remove(*key);
+            }
+            AggregateOrCreate::Create => {
+                self.insert(*key, new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replace_or_remove_matches_bids() {
+        let updates = [
+            PriceAndQuantity(1., 1.),
+            PriceAndQuantity(3., 1.),
+            PriceAndQuantity(2., 4.),
+            PriceAndQuantity(3., 2.),
+            PriceAndQuantity(1., 0.),
+            PriceAndQuantity(5., 0.),
+        ];
+        let mut bids = Bids::new();
+        let mut soa = SoaBids::new();
+        for update in updates {
+            Update::<ReplaceOrRemove>::process(&mut bids, update);
+            Update::<ReplaceOrRemove>::process(&mut soa, update);
+        }
+        assert_eq!(Bids::from(&soa), bids);
+        assert_eq!(soa.prices(), [2., 3.]);
+    }
+
+    #[test]
+    fn aggregate_or_create_matches_asks() {
+        let updates = [
+            PriceAndQuantity(1., 1),
+            PriceAndQuantity(0., 1),
+            PriceAndQuantity(1., 2),
+            PriceAndQuantity(0., -1),
+        ];
+        let mut asks = Asks::new();
+        let mut soa = SoaAsks::new();
+        for update in updates {
+            Update::<AggregateOrCreate>::process(&mut asks, update);
+            Update::<AggregateOrCreate>::process(&mut soa, update);
+        }
+        assert_eq!(Asks::from(&soa), asks);
+        assert_eq!(soa.quantities(), [3]);
+    }
+
+    #[test]
+    fn cumulative_quantity_up_to_price() {
+        let bids: SoaBids = vec![
+            PriceAndQuantity(1., 1.),
+            PriceAndQuantity(2., 2.),
+            PriceAndQuantity(3., 3.),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(bids.cumulative_quantity(&2.), 5.);
+        assert_eq!(bids.cumulative_quantity(&2.5), 3.);
+        assert_eq!(bids.cumulative_quantity(&4.), 0.);
+
+        let asks: SoaAsks = vec![
+            PriceAndQuantity(3., 3.),
+            PriceAndQuantity(2., 2.),
+            PriceAndQuantity(1., 1.),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(asks.cumulative_quantity(&2.), 3.);
+        assert_eq!(asks.cumulative_notional::<f64>(&2.), 5.);
+        assert_eq!(asks.vwap(&2.), Some(5. / 3.));
+        assert_eq!(asks.vwap(&0.5), None);
+    }
+
+    #[test]
+    fn cumulative_sums_cover_every_lane() {
+        // More levels than lanes and not a multiple of them, so the remainder is summed too.
+        let bids: SoaBids = (1..=21)
+            .map(|i| PriceAndQuantity(i as f64, (i % 5) as f64))
+            .collect();
+        for price in [0., 3., 8., 9., 17., 21.] {
+            let levels: Vec<_> = bids.iter().filter(|level| level.0 >= price).collect();
+            let quantity: f64 = levels.iter().map(|level| level.1).sum();
+            let notional: f64 = levels.iter().map(|level| level.0 * level.1).sum();
+            assert_eq!(bids.cumulative_quantity(&price), quantity);
+            assert_eq!(bids.cumulative_notional::<f64>(&price), notional);
+        }
+    }
+}